@@ -1,10 +1,16 @@
+use std::cell::OnceCell;
+
 use reqwest::{self, header};
 use serde::Deserialize;
 
-use crate::{api::digikey::product::ApiResponse, Result};
+use crate::{
+    api::{check_status, digikey::product::{ApiResponse, KeywordResponse}},
+    config::{Config, DigikeySettings},
+    Result,
+};
 mod product;
 
-pub use product::DigikeyProduct;
+pub use product::{DigikeyProduct, PriceBreak, ProductVariation};
 
 /// Response structure for OAuth token requests
 #[derive(Deserialize, Debug)]
@@ -12,57 +18,126 @@ struct TokenResponse {
     access_token: String,
 }
 
-pub fn get_product(client_id: &str, client_secret: &str, dk_mpn: &str) -> Result<DigikeyProduct> {
-    let client = reqwest::blocking::Client::new();
-
-    // Get access token
-    let params = [
-        ("client_id", &client_id),
-        ("client_secret", &client_secret),
-        ("grant_type", &"client_credentials"),
-    ];
-    let response = client
-        .post("https://api.digikey.com/v1/oauth2/token")
-        .form(&params)
-        .send()?;
-    let token_response: TokenResponse = response.json()?;
-    let token = token_response.access_token;
-
-
-    // Get Digikey product information
-    let mut headers = header::HeaderMap::new();
-    headers.insert(
-        header::AUTHORIZATION,
-        header::HeaderValue::from_str(&format!("Bearer {token}"))?,
-    );
-    headers.insert(
-        "X-DIGIKEY-Client-Id",
-        header::HeaderValue::from_str(client_id)?,
-    );
-    headers.insert(
-        "X-DIGIKEY-Locale-Site",
-        header::HeaderValue::from_static("AU"),
-    );
-    headers.insert(
-        "X-DIGIKEY-Locale-Language",
-        header::HeaderValue::from_static("en"),
-    );
-    headers.insert(
-        "X-DIGIKEY-Locale-Currency",
-        header::HeaderValue::from_static("AUD"),
-    );
-    let response = client
-        .get(format!(
-            "https://api.digikey.com/products/v4/search/{}/productdetails",
-            &dk_mpn
-        ))
-        .headers(headers)
-        .send()?;
-
-    let mut api_response: ApiResponse = response.json()?;
-
-    // Store the search SPN since DigiKey may return multiple part numbers
-    api_response.product.search_spn = Some(dk_mpn.to_owned());
-
-    Ok(api_response.product)
-}
\ No newline at end of file
+/// DigiKey API client, the access token is requested on first use and
+/// reused for every following request
+pub struct Client {
+    http: reqwest::blocking::Client,
+    client_id: String,
+    client_secret: String,
+    settings: DigikeySettings,
+    token: OnceCell<String>,
+}
+
+impl Client {
+    pub fn new(client_id: &str, client_secret: &str, settings: &DigikeySettings) -> Self {
+        Self {
+            http: reqwest::blocking::Client::new(),
+            client_id: client_id.to_owned(),
+            client_secret: client_secret.to_owned(),
+            settings: settings.clone(),
+            token: OnceCell::new(),
+        }
+    }
+
+    /// Create a client from the credentials and `[digikey]` settings in the config
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let creds = config.digikey_credentials()?;
+        Ok(Self::new(&creds.client_id, &creds.client_secret, &config.digikey_settings))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.settings.base_url(), path)
+    }
+
+    fn token(&self) -> Result<&str> {
+        if let Some(token) = self.token.get() {
+            return Ok(token);
+        }
+
+        let params = [
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+            ("grant_type", "client_credentials"),
+        ];
+        let response = self.http
+            .post(self.url("/v1/oauth2/token"))
+            .form(&params)
+            .send()?;
+        let response = check_status(response)?;
+        let token_response: TokenResponse = response.json()?;
+
+        Ok(self.token.get_or_init(|| token_response.access_token))
+    }
+
+    /// Headers for authenticated product API requests
+    fn headers(&self) -> Result<header::HeaderMap> {
+        let token = self.token()?;
+
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_str(&format!("Bearer {token}"))?,
+        );
+        headers.insert(
+            "X-DIGIKEY-Client-Id",
+            header::HeaderValue::from_str(&self.client_id)?,
+        );
+        headers.insert(
+            "X-DIGIKEY-Locale-Site",
+            header::HeaderValue::from_str(self.settings.site())?,
+        );
+        headers.insert(
+            "X-DIGIKEY-Locale-Language",
+            header::HeaderValue::from_str(self.settings.language())?,
+        );
+        headers.insert(
+            "X-DIGIKEY-Locale-Currency",
+            header::HeaderValue::from_str(self.settings.currency())?,
+        );
+        if let Some(customer_id) = &self.settings.customer_id {
+            headers.insert(
+                "X-DIGIKEY-Customer-Id",
+                header::HeaderValue::from_str(customer_id)?,
+            );
+        }
+        Ok(headers)
+    }
+
+    /// Get product details for a DigiKey part number
+    pub fn get_product(&self, dk_spn: &str) -> Result<DigikeyProduct> {
+        let response = self.http
+            .get(self.url(&format!("/products/v4/search/{dk_spn}/productdetails")))
+            .headers(self.headers()?)
+            .send()?;
+        let response = check_status(response)?;
+
+        let mut api_response: ApiResponse = response.json()?;
+
+        // Store the search SPN since DigiKey may return multiple part numbers
+        api_response.product.search_spn = Some(dk_spn.to_owned());
+
+        Ok(api_response.product)
+    }
+
+    /// Search products by keyword, returning at most `limit` results
+    pub fn search_keyword(&self, keywords: &str, limit: u32) -> Result<Vec<DigikeyProduct>> {
+        let body = serde_json::json!({
+            "Keywords": keywords,
+            "Limit": limit,
+            "Offset": 0,
+        });
+        let response = self.http
+            .post(self.url("/products/v4/search/keyword"))
+            .headers(self.headers()?)
+            .json(&body)
+            .send()?;
+        let response = check_status(response)?;
+
+        let mut search: KeywordResponse = response.json()?;
+        for product in &mut search.products {
+            product.search_spn = product.preferred_spn().map(str::to_owned);
+        }
+
+        Ok(search.products)
+    }
+}
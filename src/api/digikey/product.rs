@@ -32,6 +32,24 @@ pub struct Category {
     pub child_categories: Vec<Option<Category>>,
 }
 
+/// A quantity price break
+#[derive(Deserialize, Debug, Clone)]
+pub struct PriceBreak {
+    #[serde(rename = "BreakQuantity")]
+    pub break_qty: u32,
+    #[serde(rename = "UnitPrice")]
+    pub unit_price: f64,
+    #[serde(rename = "TotalPrice")]
+    pub total_price: f64,
+}
+
+/// Packaging of a product variation, e.g. cut tape or reel
+#[derive(Deserialize, Debug)]
+pub struct PackageType {
+    #[serde(rename = "Name")]
+    pub name: String,
+}
+
 /// Product variation information including stock and ordering details
 #[derive(Deserialize, Debug)]
 pub struct ProductVariation {
@@ -43,6 +61,11 @@ pub struct ProductVariation {
     pub qty_available_for_package_type: u32,
     #[serde(rename = "MinimumOrderQuantity")]
     pub minimum_order_qty: u32,
+    #[serde(rename = "PackageType")]
+    pub package_type: Option<PackageType>,
+    /// Price breaks in the requested currency, in increasing quantity
+    #[serde(rename = "StandardPricing", default)]
+    pub standard_pricing: Vec<PriceBreak>,
 }
 
 /// Technical parameter and its value
@@ -95,6 +118,29 @@ pub struct DigikeyProduct {
     pub category: Category,
 }
 
+impl DigikeyProduct {
+    /// DigiKey part number to order, preferring non-marketplace variations
+    pub fn preferred_spn(&self) -> Option<&str> {
+        self.product_variations.iter()
+            .find(|v| !v.market_place)
+            .or_else(|| self.product_variations.first())
+            .map(|v| v.digikey_product_number.as_str())
+    }
+
+    /// The variation for a DigiKey part number, ignoring case
+    pub fn variation(&self, dk_spn: &str) -> Option<&ProductVariation> {
+        self.product_variations.iter()
+            .find(|v| v.digikey_product_number.eq_ignore_ascii_case(dk_spn.trim()))
+    }
+}
+
+/// Wrapper for DigiKey keyword search responses
+#[derive(Deserialize, Debug)]
+pub struct KeywordResponse {
+    #[serde(rename = "Products")]
+    pub products: Vec<DigikeyProduct>,
+}
+
 /// Wrapper for DigiKey API responses containing product information
 #[derive(Deserialize, Debug)]
 pub struct ApiResponse {
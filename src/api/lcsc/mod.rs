@@ -0,0 +1,92 @@
+use crate::{
+    api::{check_status, lcsc::product::{DetailResponse, SearchResponse}},
+    config::{Config, LcscSettings},
+    Error, Result,
+};
+mod product;
+
+pub use product::LcscProduct;
+
+/// Whether a part number looks like an LCSC part number, e.g. "C2040"
+pub fn is_lcsc_code(s: &str) -> bool {
+    let s = s.trim();
+    s.len() > 1
+        && s.starts_with(['C', 'c'])
+        && s[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+/// LCSC catalog client, no credentials are needed
+pub struct Client {
+    http: reqwest::blocking::Client,
+    base_url: String,
+}
+
+impl Client {
+    pub fn new(settings: &LcscSettings) -> Self {
+        Self {
+            http: reqwest::blocking::Client::new(),
+            base_url: settings.base_url().to_owned(),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(&config.lcsc_settings)
+    }
+
+    /// Get product details for an LCSC part number
+    pub fn get_product(&self, code: &str) -> Result<LcscProduct> {
+        let response = self.http
+            .get(format!("{}/ftps/wm/product/detail", self.base_url))
+            .query(&[("productCode", code.trim().to_uppercase())])
+            .send()?;
+        let response = check_status(response)?;
+
+        let detail: DetailResponse = response.json()?;
+        match detail.result {
+            Some(product) if detail.code == 200 => Ok(product),
+            _ => Err(Error::Api { status: 404, body: format!("LCSC part '{code}' not found") }),
+        }
+    }
+
+    /// Search LCSC by keyword
+    pub fn search(&self, keyword: &str) -> Result<Vec<LcscProduct>> {
+        let response = self.http
+            .get(format!("{}/ftps/wm/search/global", self.base_url))
+            .query(&[("keyword", keyword)])
+            .send()?;
+        let response = check_status(response)?;
+
+        let search: SearchResponse = response.json()?;
+        Ok(search.result
+            .and_then(|r| r.result)
+            .map(|r| r.products)
+            .unwrap_or_default())
+    }
+
+    /// Find products whose MPN exactly matches, ignoring case
+    pub fn find_by_mpn(&self, mpn: &str) -> Result<Vec<LcscProduct>> {
+        Ok(self.search(mpn)?
+            .into_iter()
+            .filter(|p| p.mpn.eq_ignore_ascii_case(mpn.trim()))
+            .collect())
+    }
+
+    /// Look up a product by LCSC part number, or by an exact MPN match
+    pub fn lookup(&self, code_or_mpn: &str) -> Result<LcscProduct> {
+        if is_lcsc_code(code_or_mpn) {
+            return self.get_product(code_or_mpn);
+        }
+        let mut matches = self.find_by_mpn(code_or_mpn)?;
+        match matches.len() {
+            0 => Err(Error::Api { status: 404, body: format!("No LCSC part with MPN '{code_or_mpn}'") }),
+            1 => Ok(matches.remove(0)),
+            n => {
+                let codes: Vec<&str> = matches.iter().map(|p| p.code.as_str()).collect();
+                Err(format!(
+                    "{n} LCSC parts match MPN '{code_or_mpn}' ({}), use the LCSC part number instead",
+                    codes.join(", ")
+                ).into())
+            },
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! LCSC product data structures.
+//!
+//! Defines the types used to deserialize LCSC catalog responses. Fields
+//! LCSC sometimes leaves out are optional.
+
+#![allow(dead_code)]
+
+use serde::Deserialize;
+
+/// Product information from the LCSC catalog
+#[derive(Deserialize, Debug, Clone)]
+pub struct LcscProduct {
+    /// LCSC part number, e.g. "C2040"
+    #[serde(rename = "productCode")]
+    pub code: String,
+    #[serde(rename = "productModel")]
+    pub mpn: String,
+    #[serde(rename = "brandNameEn")]
+    pub manufacturer: Option<String>,
+    #[serde(rename = "encapStandard")]
+    pub package: Option<String>,
+    #[serde(rename = "pdfUrl")]
+    pub datasheet_url: Option<String>,
+    #[serde(rename = "productIntroEn")]
+    pub description: Option<String>,
+    #[serde(rename = "catalogName")]
+    pub category: Option<String>,
+    #[serde(rename = "stockNumber")]
+    pub stock: Option<u64>,
+}
+
+/// Wrapper for product detail responses
+#[derive(Deserialize, Debug)]
+pub struct DetailResponse {
+    pub code: u32,
+    pub result: Option<LcscProduct>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchResultList {
+    #[serde(rename = "productList", default)]
+    pub products: Vec<LcscProduct>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchResult {
+    #[serde(rename = "productSearchResultVO")]
+    pub result: Option<SearchResultList>,
+}
+
+/// Wrapper for keyword search responses
+#[derive(Deserialize, Debug)]
+pub struct SearchResponse {
+    pub code: u32,
+    pub result: Option<SearchResult>,
+}
@@ -1,2 +1,16 @@
-pub(crate) mod digikey;
+use crate::{Error, Result};
 
+pub mod digikey;
+pub mod lcsc;
+
+/// Turn non-success HTTP responses into API errors carrying the body
+pub(crate) fn check_status(response: reqwest::blocking::Response) -> Result<reqwest::blocking::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    Err(Error::Api {
+        status: status.as_u16(),
+        body: response.text().unwrap_or_default(),
+    })
+}
@@ -0,0 +1,121 @@
+//! Parsing of ECIA / ANSI MH10.8.2 2D barcode labels.
+//!
+//! Distributor bag labels (DigiKey, Mouser, ...) encode a message like
+//! `[)>␞06␝P296-6501-1-ND␝1PTPS7A0533PDBVR␝Q10␞␄`, where each field starts
+//! with a data identifier such as `P` (customer part number, the supplier
+//! part number on DigiKey labels), `1P` (manufacturer part number) or `Q`
+//! (quantity). Scanners acting as keyboards often cannot type the control
+//! characters, so common textual stand-ins like `{GS}` and `<GS>` are
+//! accepted as separators too.
+
+use serde::Serialize;
+
+use crate::{Error, Result};
+
+const RS: char = '\x1e';
+const GS: char = '\x1d';
+const EOT: char = '\x04';
+
+/// Fields decoded from a label
+#[derive(Debug, Default, Serialize)]
+pub struct Label {
+    /// `P`, the customer or supplier part number
+    pub supplier_pn: Option<String>,
+    /// `1P`, the manufacturer part number
+    pub mpn: Option<String>,
+    /// `Q`, the quantity in the bag
+    pub qty: Option<u32>,
+    /// `1T`, the lot code
+    pub lot: Option<String>,
+    /// `10D` or `9D`, the date code
+    pub date_code: Option<String>,
+    /// `4L`, the country of origin
+    pub country: Option<String>,
+    /// `K`, the purchase order number
+    pub po: Option<String>,
+}
+
+/// Replace typed stand-ins for control characters with the real characters
+fn normalise(s: &str) -> String {
+    let mut s = s.trim().to_owned();
+    for (from, to) in [
+        ("{RS}", RS), ("<RS>", RS), ("\u{241e}", RS),
+        ("{GS}", GS), ("<GS>", GS), ("\u{241d}", GS),
+        ("{EOT}", EOT), ("<EOT>", EOT), ("\u{2404}", EOT),
+    ] {
+        s = s.replace(from, &to.to_string());
+    }
+    s
+}
+
+/// Parse a decoded label string
+pub fn parse(input: &str) -> Result<Label> {
+    let s = normalise(input);
+    let body = s.strip_prefix("[)>")
+        .ok_or_else(|| Error::Parse("Barcode does not start with the '[)>' header".into()))?;
+
+    // Header is followed by RS and the format, "06" for data identifiers
+    let body = body.trim_start_matches(RS);
+    let body = body.strip_prefix("06")
+        .ok_or_else(|| Error::Parse("Only format 06 barcodes are supported".into()))?;
+
+    let mut label = Label::default();
+    for field in body.split([GS, RS, EOT]).filter(|f| !f.is_empty()) {
+        // Longest identifiers first so `1P` is not read as `1` + `P...`
+        let value = |id: &str| field.strip_prefix(id).map(str::to_owned).filter(|v| !v.is_empty());
+        if let Some(v) = value("1P") {
+            label.mpn = Some(v);
+        } else if let Some(v) = value("1T") {
+            label.lot = Some(v);
+        } else if let Some(v) = value("10D").or_else(|| value("9D")) {
+            label.date_code = Some(v);
+        } else if let Some(v) = value("4L") {
+            label.country = Some(v);
+        } else if field.starts_with(|c: char| c.is_ascii_digit()) {
+            // Other numbered identifiers (1K, 11K, 11Z, ...) are not used
+        } else if let Some(v) = value("P") {
+            label.supplier_pn = Some(v);
+        } else if let Some(v) = value("Q") {
+            label.qty = v.parse().ok();
+        } else if let Some(v) = value("K") {
+            label.po = Some(v);
+        }
+    }
+
+    if label.supplier_pn.is_none() && label.mpn.is_none() {
+        return Err(Error::Parse("Barcode has no supplier or manufacturer part number".into()));
+    }
+    Ok(label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_control_characters() {
+        let label = parse("[)>\x1e06\x1dP296-6501-1-ND\x1d1PTPS7A0533PDBVR\x1dQ10\x1d1T12345\x1d10D2231\x1d4LCN\x1e\x04").unwrap();
+        assert_eq!(label.supplier_pn.as_deref(), Some("296-6501-1-ND"));
+        assert_eq!(label.mpn.as_deref(), Some("TPS7A0533PDBVR"));
+        assert_eq!(label.qty, Some(10));
+        assert_eq!(label.lot.as_deref(), Some("12345"));
+        assert_eq!(label.date_code.as_deref(), Some("2231"));
+        assert_eq!(label.country.as_deref(), Some("CN"));
+    }
+
+    #[test]
+    fn accepts_typed_separators() {
+        let label = parse("[)>{RS}06<GS>KPO-1<GS>1K7788<GS>1PRC0402FR-07120KL{GS}Q250{RS}{EOT}").unwrap();
+        assert_eq!(label.supplier_pn, None);
+        assert_eq!(label.mpn.as_deref(), Some("RC0402FR-07120KL"));
+        assert_eq!(label.po.as_deref(), Some("PO-1"));
+        assert_eq!(label.qty, Some(250));
+    }
+
+    #[test]
+    fn rejects_other_labels() {
+        assert!(parse("296-6501-1-ND").is_err());
+        assert!(parse("[)>{RS}05{GS}P296-6501-1-ND").is_err());
+        assert!(parse("[)>{RS}06{GS}Q10{RS}{EOT}").is_err());
+    }
+}
@@ -0,0 +1,448 @@
+//! Bills of materials from KiCad designs.
+//!
+//! Components are read from a schematic (`.kicad_sch`, following
+//! hierarchical sheets), an XML netlist export or an S-expression `.net`
+//! netlist, then matched against the library by the part ID, taken from an
+//! `id` field or the symbol name of the DB library reference
+//! (`<LibraryNickname>:<ID>`).
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{part::Part, Error, Result};
+
+mod sexpr;
+mod xml;
+
+use sexpr::Sexpr;
+
+/// A placed component, the units of a multi-unit symbol are merged into one
+#[derive(Debug, Clone)]
+pub struct Component {
+    pub reference: String,
+    pub lib_id: String,
+    pub value: String,
+    /// Fields and properties by name
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Component {
+    /// Candidate library IDs, the `id` field then the symbol name
+    fn candidate_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.fields.iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("id"))
+            .map(|(_, v)| v.trim())
+            .collect();
+        let name = self.lib_id.rsplit(':').next().unwrap_or_default();
+        ids.push(name.rsplit('/').next().unwrap_or_default());
+        ids
+    }
+}
+
+/// Read the components of a schematic or netlist, skipping power symbols
+/// and components excluded from the BOM or marked do not populate
+pub fn load_components(path: &Path) -> Result<Vec<Component>> {
+    let content = fs::read_to_string(path)?;
+    let parse_error = |e: Error| Error::Parse(format!("{}: {e}", path.display()));
+
+    let mut components = if content.trim_start().starts_with('<') {
+        netlist_xml_components(&xml::parse(&content).map_err(parse_error)?)
+    } else {
+        let root = sexpr::parse(&content).map_err(parse_error)?;
+        match root.head() {
+            Some("kicad_sch") => schematic_components(path, root)?,
+            Some("export") => netlist_sexpr_components(&root),
+            other => return Err(Error::Parse(format!(
+                "{}: expected a KiCad schematic or netlist, found '{}'",
+                path.display(),
+                other.unwrap_or_default()
+            ))),
+        }
+    };
+
+    components.retain(|c| !c.reference.starts_with('#'));
+    if let Some(c) = components.iter().find(|c| c.reference.is_empty() || c.reference.ends_with('?')) {
+        return Err(Error::Parse(format!(
+            "{}: {} has no reference, annotate the schematic first",
+            path.display(),
+            if c.reference.is_empty() { &c.lib_id } else { &c.reference }
+        )));
+    }
+    // Units of a multi-unit symbol share the reference and symbol
+    components.sort_by(|a, b| (natural_key(&a.reference), &a.lib_id).cmp(&(natural_key(&b.reference), &b.lib_id)));
+    components.dedup_by(|a, b| a.reference == b.reference && a.lib_id == b.lib_id);
+    Ok(components)
+}
+
+/// Components of a schematic and every sheet below it
+fn schematic_components(path: &Path, root: Sexpr) -> Result<Vec<Component>> {
+    let mut components = Vec::new();
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    let mut queue = vec![(path.to_path_buf(), Some(root))];
+
+    while let Some((path, sheet)) = queue.pop() {
+        if !visited.insert(fs::canonicalize(&path).unwrap_or_else(|_| path.clone())) {
+            continue;
+        }
+        let sheet = match sheet {
+            Some(sheet) => sheet,
+            None => sexpr::parse(&fs::read_to_string(&path)?)
+                .map_err(|e| Error::Parse(format!("{}: {e}", path.display())))?,
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+
+        for child in sheet.items() {
+            match child.head() {
+                Some("symbol") => components.extend(schematic_symbol(child)),
+                Some("sheet") => {
+                    let file = properties(child).into_iter()
+                        .find(|(k, _)| k == "Sheetfile" || k == "Sheet file")
+                        .map(|(_, v)| v);
+                    if let Some(file) = file {
+                        queue.push((dir.join(file), None));
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+    Ok(components)
+}
+
+fn properties(node: &Sexpr) -> BTreeMap<String, String> {
+    node.children("property")
+        .filter_map(|p| Some((p.atom(1)?.to_owned(), p.atom(2)?.to_owned())))
+        .collect()
+}
+
+/// A placed schematic symbol, one component per sheet instance
+fn schematic_symbol(symbol: &Sexpr) -> Vec<Component> {
+    if symbol.value("in_bom") == Some("no") || symbol.value("dnp") == Some("yes") {
+        return Vec::new();
+    }
+    let fields = properties(symbol);
+    let component = Component {
+        reference: fields.get("Reference").cloned().unwrap_or_default(),
+        lib_id: symbol.value("lib_id").unwrap_or_default().to_owned(),
+        value: fields.get("Value").cloned().unwrap_or_default(),
+        fields,
+    };
+
+    // Sheets used more than once list a reference for every instance
+    let references: Vec<&str> = symbol.children("instances")
+        .flat_map(|i| i.children("project"))
+        .flat_map(|p| p.children("path"))
+        .filter_map(|p| p.value("reference"))
+        .collect();
+    if references.is_empty() {
+        return vec![component];
+    }
+    references.into_iter()
+        .map(|reference| Component { reference: reference.to_owned(), ..component.clone() })
+        .collect()
+}
+
+/// Components of an XML netlist export
+fn netlist_xml_components(export: &xml::Element) -> Vec<Component> {
+    let Some(comps) = export.child("components") else { return Vec::new() };
+    comps.children("comp")
+        .filter(|comp| {
+            !comp.children("property")
+                .any(|p| matches!(p.attr("name"), Some("exclude_from_bom" | "dnp")))
+        })
+        .map(|comp| {
+            let mut fields: BTreeMap<String, String> = comp.children("property")
+                .filter_map(|p| Some((p.attr("name")?.to_owned(), p.attr("value").unwrap_or_default().to_owned())))
+                .collect();
+            if let Some(list) = comp.child("fields") {
+                for field in list.children("field") {
+                    if let Some(name) = field.attr("name") {
+                        fields.insert(name.to_owned(), field.text.trim().to_owned());
+                    }
+                }
+            }
+            let libsource = comp.child("libsource");
+            Component {
+                reference: comp.attr("ref").unwrap_or_default().to_owned(),
+                lib_id: format!(
+                    "{}:{}",
+                    libsource.and_then(|l| l.attr("lib")).unwrap_or_default(),
+                    libsource.and_then(|l| l.attr("part")).unwrap_or_default(),
+                ),
+                value: comp.child_text("value").unwrap_or_default().to_owned(),
+                fields,
+            }
+        })
+        .collect()
+}
+
+/// Components of an S-expression `.net` netlist
+fn netlist_sexpr_components(export: &Sexpr) -> Vec<Component> {
+    let Some(comps) = export.child("components") else { return Vec::new() };
+    comps.children("comp")
+        .filter(|comp| {
+            !comp.children("property")
+                .any(|p| matches!(p.value("name"), Some("exclude_from_bom" | "dnp")))
+        })
+        .map(|comp| {
+            let mut fields: BTreeMap<String, String> = comp.children("property")
+                .filter_map(|p| Some((p.value("name")?.to_owned(), p.value("value").unwrap_or_default().to_owned())))
+                .collect();
+            if let Some(list) = comp.child("fields") {
+                for field in list.children("field") {
+                    if let (Some(name), Some(value)) = (field.value("name"), field.atom(2)) {
+                        fields.insert(name.to_owned(), value.to_owned());
+                    }
+                }
+            }
+            let libsource = comp.child("libsource");
+            Component {
+                reference: comp.value("ref").unwrap_or_default().to_owned(),
+                lib_id: format!(
+                    "{}:{}",
+                    libsource.and_then(|l| l.value("lib")).unwrap_or_default(),
+                    libsource.and_then(|l| l.value("part")).unwrap_or_default(),
+                ),
+                value: comp.value("value").unwrap_or_default().to_owned(),
+                fields,
+            }
+        })
+        .collect()
+}
+
+/// Sort key so R2 comes before R10
+fn natural_key(reference: &str) -> (String, u64, String) {
+    let prefix: String = reference.chars().take_while(|c| !c.is_ascii_digit()).collect();
+    let rest = &reference[prefix.len()..];
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    (prefix, digits.parse().unwrap_or(0), rest[digits.len()..].to_owned())
+}
+
+/// A problem with a BOM line
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Problem {
+    NotInLibrary,
+    Obsolete,
+    StatusIssues,
+    MissingMpn,
+    MissingSpn,
+}
+
+impl Problem {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Problem::NotInLibrary => "not in library",
+            Problem::Obsolete => "obsolete",
+            Problem::StatusIssues => "status is issues",
+            Problem::MissingMpn => "no MPN",
+            Problem::MissingSpn => "no supplier part number",
+        }
+    }
+}
+
+/// Components sharing a library part, or for unknown parts the same
+/// symbol and value
+#[derive(Debug, Serialize)]
+pub struct BomLine {
+    pub id: Option<String>,
+    pub lib_id: String,
+    pub value: String,
+    pub qty: usize,
+    pub references: Vec<String>,
+    pub description: Option<String>,
+    pub manufacturer: Option<String>,
+    pub mpn: Option<String>,
+    pub status: Option<String>,
+    pub problems: Vec<Problem>,
+    /// The matched library part
+    #[serde(skip)]
+    pub part: Option<Part>,
+}
+
+/// Group components into BOM lines and check each against the library
+pub fn build(components: &[Component], parts: &[Part]) -> Vec<BomLine> {
+    let by_id: HashMap<&str, &Part> = parts.iter()
+        .filter_map(|p| Some((p.id.as_deref()?, p)))
+        .collect();
+
+    let mut lines: BTreeMap<(String, String), BomLine> = BTreeMap::new();
+    for component in components {
+        let part = component.candidate_ids().into_iter().find_map(|id| by_id.get(id).copied());
+        let key = match part {
+            Some(part) => (part.id.clone().unwrap_or_default(), String::new()),
+            None => (format!("~{}", component.lib_id), component.value.clone()),
+        };
+        let line = lines.entry(key).or_insert_with(|| new_line(component, part));
+        line.qty += 1;
+        line.references.push(component.reference.clone());
+    }
+    lines.into_values().collect()
+}
+
+fn new_line(component: &Component, part: Option<&Part>) -> BomLine {
+    let mut problems = Vec::new();
+    match part {
+        None => problems.push(Problem::NotInLibrary),
+        Some(part) => {
+            if part.status.eq_ignore_ascii_case(Part::OBSOLETE) {
+                problems.push(Problem::Obsolete);
+            }
+            if part.status.eq_ignore_ascii_case(Part::ISSUES) {
+                problems.push(Problem::StatusIssues);
+            }
+            if part.mpn.is_none() {
+                problems.push(Problem::MissingMpn);
+            }
+            if part.spn1.is_none() && part.spn2.is_none() {
+                problems.push(Problem::MissingSpn);
+            }
+        },
+    }
+
+    BomLine {
+        id: part.and_then(|p| p.id.clone()),
+        lib_id: component.lib_id.clone(),
+        value: part.map(|p| p.value.clone()).unwrap_or_else(|| component.value.clone()),
+        qty: 0,
+        references: Vec::new(),
+        description: part.map(|p| p.description.clone()),
+        manufacturer: part.and_then(|p| p.manufacturer.clone()),
+        mpn: part.and_then(|p| p.mpn.clone()),
+        status: part.map(|p| p.status.clone()),
+        problems,
+        part: part.cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_dir;
+
+    fn part(id: &str, status: &str) -> Part {
+        let mut part = Part::for_test(id, "capacitor");
+        part.status = status.into();
+        part.mpn = Some(format!("MPN-{id}"));
+        part.spn1 = Some(format!("SPN-{id}"));
+        part
+    }
+
+    fn references(components: &[Component]) -> Vec<&str> {
+        components.iter().map(|c| c.reference.as_str()).collect()
+    }
+
+    #[test]
+    fn schematic_follows_nested_sheets() {
+        let dir = test_dir("sheets");
+        fs::write(dir.join("board.kicad_sch"), r##"(kicad_sch (version 20231120)
+            (symbol (lib_id "Partman:CAP-0001") (in_bom yes) (dnp no)
+              (property "Reference" "C1") (property "Value" "100n"))
+            (symbol (lib_id "Partman:CAP-0001") (in_bom no)
+              (property "Reference" "C2") (property "Value" "100n"))
+            (symbol (lib_id "Partman:CAP-0001") (dnp yes)
+              (property "Reference" "C3") (property "Value" "100n"))
+            (symbol (lib_id "power:GND") (property "Reference" "#PWR01") (property "Value" "GND"))
+            (sheet (property "Sheetname" "Power") (property "Sheetfile" "power/power.kicad_sch")))"##).unwrap();
+        fs::create_dir_all(dir.join("power")).unwrap();
+        fs::write(dir.join("power/power.kicad_sch"), r#"(kicad_sch (version 20231120)
+            (symbol (lib_id "Partman:RES-0001") (property "Reference" "R?") (property "Value" "1k")
+              (instances (project "board"
+                (path "/a/p1" (reference "R10") (unit 1))
+                (path "/a/p2" (reference "R2") (unit 1)))))
+            (sheet (property "Sheetname" "LDO") (property "Sheetfile" "ldo.kicad_sch"))
+            (sheet (property "Sheetname" "Back") (property "Sheetfile" "../board.kicad_sch")))"#).unwrap();
+        fs::write(dir.join("power/ldo.kicad_sch"), r#"(kicad_sch (version 20231120)
+            (symbol (lib_id "Regulator:LDO") (property "Reference" "U1") (property "Value" "LDO")
+              (property "id" "IC-0001")))"#).unwrap();
+
+        let components = load_components(&dir.join("board.kicad_sch")).unwrap();
+        assert_eq!(references(&components), ["C1", "R2", "R10", "U1"]);
+        assert_eq!(components[3].candidate_ids(), ["IC-0001", "LDO"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn xml_netlist_skips_excluded_components() {
+        let dir = test_dir("netlist");
+        let path = dir.join("board.xml");
+        fs::write(&path, r#"<?xml version="1.0" encoding="utf-8"?>
+            <export version="E"><components>
+              <comp ref="R1"><value>10k</value><libsource lib="Partman" part="RES-0001"/>
+                <fields><field name="Tolerance">1%</field></fields></comp>
+              <comp ref="R2"><value>10k</value><libsource lib="Partman" part="RES-0001"/>
+                <property name="dnp"/></comp>
+              <comp ref="R3"><value>10k</value><libsource lib="Partman" part="RES-0001"/>
+                <property name="exclude_from_bom"/></comp>
+            </components></export>"#).unwrap();
+
+        let components = load_components(&path).unwrap();
+        assert_eq!(references(&components), ["R1"]);
+        assert_eq!(components[0].lib_id, "Partman:RES-0001");
+        assert_eq!(components[0].fields.get("Tolerance").map(String::as_str), Some("1%"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn build_groups_components_by_part() {
+        let component = |reference: &str, lib_id: &str, value: &str| Component {
+            reference: reference.into(),
+            lib_id: lib_id.into(),
+            value: value.into(),
+            fields: BTreeMap::new(),
+        };
+        let components = [
+            component("C1", "Partman:CAP-0001", "100n"),
+            component("C2", "Partman-capacitor:CAP-0001", "100n"),
+            component("C3", "Partman:CAP-0002", "1u"),
+            component("R1", "Device:R", "10k"),
+            component("R2", "Device:R", "10k"),
+            component("R3", "Device:R", "1k"),
+        ];
+        let parts = [part("CAP-0001", "verified"), part("CAP-0002", Part::OBSOLETE)];
+
+        let lines = build(&components, &parts);
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].id.as_deref(), Some("CAP-0001"));
+        assert_eq!(lines[0].references, ["C1", "C2"]);
+        assert!(lines[0].problems.is_empty());
+        assert_eq!(lines[1].problems, [Problem::Obsolete]);
+
+        let unknown: Vec<(&str, usize)> = lines[2..].iter().map(|l| (l.value.as_str(), l.qty)).collect();
+        assert_eq!(unknown, [("10k", 2), ("1k", 1)]);
+        assert!(lines[2..].iter().all(|l| l.problems == [Problem::NotInLibrary]));
+    }
+
+    #[test]
+    fn references_sort_naturally() {
+        let mut refs = ["R10", "C1", "R2", "R1a", "R1"];
+        refs.sort_by_key(|r| natural_key(r));
+        assert_eq!(refs, ["C1", "R1", "R1a", "R2", "R10"]);
+    }
+
+    #[test]
+    fn merges_only_units_of_the_same_symbol() {
+        let dir = test_dir("units");
+        let path = dir.join("board.kicad_sch");
+        fs::write(&path, r#"(kicad_sch (version 20231120)
+            (symbol (lib_id "Partman:IC-0001") (unit 1) (property "Reference" "U1") (property "Value" "LM358"))
+            (symbol (lib_id "Partman:IC-0001") (unit 2) (property "Reference" "U1") (property "Value" "LM358"))
+            (symbol (lib_id "Partman:RES-0001") (property "Reference" "R1") (property "Value" "10k"))
+            (symbol (lib_id "Partman:RES-0002") (property "Reference" "R1") (property "Value" "1k")))"#).unwrap();
+        let components = load_components(&path).unwrap();
+        assert_eq!(references(&components), ["R1", "R1", "U1"]);
+
+        fs::write(&path, r##"(kicad_sch (version 20231120)
+            (symbol (lib_id "Partman:RES-0001") (property "Reference" "R?") (property "Value" "10k"))
+            (symbol (lib_id "Partman:RES-0001") (property "Reference" "R?") (property "Value" "10k"))
+            (symbol (lib_id "power:GND") (property "Reference" "#PWR?") (property "Value" "GND")))"##).unwrap();
+        let err = load_components(&path).unwrap_err();
+        assert!(matches!(err, Error::Parse(ref message) if message.contains("R? has no reference, annotate")));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+//! Minimal reader for the S-expression files KiCad writes.
+
+use crate::{Error, Result};
+
+#[derive(Debug, Clone)]
+pub enum Sexpr {
+    Atom(String),
+    List(Vec<Sexpr>),
+}
+
+impl Sexpr {
+    pub fn as_atom(&self) -> Option<&str> {
+        match self {
+            Sexpr::Atom(s) => Some(s),
+            Sexpr::List(_) => None,
+        }
+    }
+
+    /// The items of a list, empty for an atom
+    pub fn items(&self) -> &[Sexpr] {
+        match self {
+            Sexpr::List(items) => items,
+            Sexpr::Atom(_) => &[],
+        }
+    }
+
+    /// The leading atom of a list, e.g. `symbol` for `(symbol ...)`
+    pub fn head(&self) -> Option<&str> {
+        self.items().first().and_then(Sexpr::as_atom)
+    }
+
+    /// The atom at position `i` of a list, the head is position 0
+    pub fn atom(&self, i: usize) -> Option<&str> {
+        self.items().get(i).and_then(Sexpr::as_atom)
+    }
+
+    /// Child lists with the given head
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Sexpr> + 'a {
+        self.items().iter().filter(move |c| c.head() == Some(name))
+    }
+
+    /// First child list with the given head
+    pub fn child(&self, name: &str) -> Option<&Sexpr> {
+        self.items().iter().find(|c| c.head() == Some(name))
+    }
+
+    /// Value of a `(name value)` child
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.child(name).and_then(|c| c.atom(1))
+    }
+}
+
+/// Parse the first expression in the input
+pub fn parse(input: &str) -> Result<Sexpr> {
+    let mut chars = input.chars().peekable();
+    let mut stack: Vec<Vec<Sexpr>> = Vec::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => stack.push(Vec::new()),
+            ')' => {
+                let list = Sexpr::List(stack.pop().ok_or_else(|| Error::Parse("Unbalanced ')'".into()))?);
+                match stack.last_mut() {
+                    Some(parent) => parent.push(list),
+                    None => return Ok(list),
+                }
+            },
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => s.push('\n'),
+                            Some('t') => s.push('\t'),
+                            Some(other) => s.push(other),
+                            None => break,
+                        },
+                        Some(other) => s.push(other),
+                        None => return Err(Error::Parse("Unterminated string".into())),
+                    }
+                }
+                push_atom(&mut stack, s)?;
+            },
+            c if c.is_whitespace() => {},
+            c => {
+                let mut s = String::from(c);
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == '(' || next == ')' {
+                        break;
+                    }
+                    s.push(next);
+                    chars.next();
+                }
+                push_atom(&mut stack, s)?;
+            },
+        }
+    }
+
+    Err(Error::Parse("Unexpected end of S-expression".into()))
+}
+
+fn push_atom(stack: &mut [Vec<Sexpr>], atom: String) -> Result<()> {
+    stack.last_mut()
+        .ok_or_else(|| Error::Parse("Expected '(' at start of S-expression".into()))?
+        .push(Sexpr::Atom(atom));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_lists() {
+        let root = parse("(kicad_sch (version 20231120) (symbol (lib_id \"Device:R\") (in_bom yes)))").unwrap();
+        assert_eq!(root.head(), Some("kicad_sch"));
+        assert_eq!(root.value("version"), Some("20231120"));
+        let symbol = root.child("symbol").unwrap();
+        assert_eq!(symbol.value("lib_id"), Some("Device:R"));
+        assert_eq!(symbol.value("in_bom"), Some("yes"));
+    }
+
+    #[test]
+    fn unescapes_strings() {
+        let root = parse(r#"(property "Value" "10k \"big\"\n(1%)\\")"#).unwrap();
+        assert_eq!(root.atom(2), Some("10k \"big\"\n(1%)\\"));
+    }
+
+    #[test]
+    fn rejects_broken_input() {
+        assert!(parse("(a (b)").is_err());
+        assert!(parse("(a \"open)").is_err());
+        assert!(parse("a (b)").is_err());
+        assert!(parse(")").is_err());
+    }
+}
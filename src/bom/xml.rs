@@ -0,0 +1,204 @@
+//! Minimal XML reader, enough for KiCad's XML netlist export.
+//!
+//! Handles elements, attributes, text and the predefined and numeric
+//! entities. DTDs, CDATA and namespaces are not supported.
+
+use crate::{Error, Result};
+
+#[derive(Debug, Default)]
+pub struct Element {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String,
+}
+
+impl Element {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// Trimmed text of a child element
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|c| c.text.trim())
+    }
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else { break };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity.strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(|n| n.ok())
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn parse_attrs(s: &str) -> Result<Vec<(String, String)>> {
+    let mut attrs = Vec::new();
+    let mut rest = s.trim();
+    while !rest.is_empty() {
+        let eq = rest.find('=').ok_or_else(|| Error::Parse(format!("Invalid XML attribute '{rest}'")))?;
+        let name = rest[..eq].trim().to_owned();
+        let value_part = rest[eq + 1..].trim_start();
+        let quote = value_part.chars().next()
+            .filter(|q| *q == '"' || *q == '\'')
+            .ok_or_else(|| Error::Parse(format!("Unquoted XML attribute '{name}'")))?;
+        let end = value_part[1..].find(quote)
+            .ok_or_else(|| Error::Parse(format!("Unterminated XML attribute '{name}'")))?;
+        attrs.push((name, unescape(&value_part[1..end + 1])));
+        rest = value_part[end + 2..].trim_start();
+    }
+    Ok(attrs)
+}
+
+/// Index of the `>` ending the tag at the start of `s`, skipping quoted
+/// attribute values which may contain a literal `>`
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {},
+        }
+    }
+    None
+}
+
+/// Parse a document, returning its root element
+pub fn parse(input: &str) -> Result<Element> {
+    let mut stack: Vec<Element> = vec![Element::default()];
+    let mut rest = input;
+
+    while let Some(start) = rest.find('<') {
+        let text = &rest[..start];
+        if let Some(current) = stack.last_mut() {
+            current.text.push_str(&unescape(text));
+        }
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after.find("-->").ok_or_else(|| Error::Parse("Unterminated XML comment".into()))?;
+            rest = &after[end + 3..];
+            continue;
+        }
+        let end = tag_end(rest).ok_or_else(|| Error::Parse("Unterminated XML tag".into()))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            let element = stack.pop().filter(|e| e.name == name.trim())
+                .ok_or_else(|| Error::Parse(format!("Unexpected closing tag '</{}>'", name.trim())))?;
+            stack.last_mut()
+                .ok_or_else(|| Error::Parse("Unbalanced XML".into()))?
+                .children.push(element);
+            continue;
+        }
+
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (name, attrs) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        let element = Element {
+            name: name.to_owned(),
+            attrs: parse_attrs(attrs)?,
+            ..Default::default()
+        };
+        if self_closing {
+            stack.last_mut()
+                .ok_or_else(|| Error::Parse("Unbalanced XML".into()))?
+                .children.push(element);
+        } else {
+            stack.push(element);
+        }
+    }
+
+    let mut document = stack.pop().filter(|_| stack.is_empty())
+        .ok_or_else(|| Error::Parse("Unclosed XML element".into()))?;
+    if document.children.is_empty() {
+        return Err(Error::Parse("XML document has no root element".into()));
+    }
+    Ok(document.children.swap_remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_elements_and_attributes() {
+        let root = parse(r#"<?xml version="1.0"?>
+            <!-- exported by eeschema -->
+            <export version='E'>
+              <components>
+                <comp ref="R1"><value>10k</value><libsource lib="Device" part="R"/></comp>
+              </components>
+            </export>"#).unwrap();
+        assert_eq!(root.name, "export");
+        assert_eq!(root.attr("version"), Some("E"));
+        let comp = root.child("components").unwrap().child("comp").unwrap();
+        assert_eq!(comp.attr("ref"), Some("R1"));
+        assert_eq!(comp.child_text("value"), Some("10k"));
+        assert_eq!(comp.child("libsource").unwrap().attr("part"), Some("R"));
+    }
+
+    #[test]
+    fn decodes_entities() {
+        let root = parse(r#"<v name="a &quot;b&quot; &lt;c&gt;">10k &amp; 1&#x3A9; &#8486; &bogus;</v>"#).unwrap();
+        assert_eq!(root.attr("name"), Some("a \"b\" <c>"));
+        assert_eq!(root.text, "10k & 1\u{3a9} \u{2126} &bogus;");
+    }
+
+    #[test]
+    fn allows_gt_in_attribute_values() {
+        let root = parse(r#"<comp ref="U1"><field name="Note" value='Vin > 5V'/><value>a>b</value></comp>"#).unwrap();
+        assert_eq!(root.child("field").unwrap().attr("value"), Some("Vin > 5V"));
+        assert_eq!(root.child_text("value"), Some("a>b"));
+        assert!(parse(r#"<comp ref="U1>"#).is_err());
+    }
+
+    #[test]
+    fn rejects_mismatched_tags() {
+        assert!(parse("<a><b></a></b>").is_err());
+        assert!(parse("<a>").is_err());
+        assert!(parse("<a x=1/>").is_err());
+        assert!(parse("just text").is_err());
+    }
+}
@@ -1,17 +1,26 @@
-use std::{ffi::OsStr, fs, path::Path};
+use std::{collections::HashMap, fs, io::{self, Read}, path::{Path, PathBuf}, time::Duration};
 
-use crate::{
-    Result, api::digikey, config::{Config, Secrets}, data, editor::{Form, edit, edit_file, process_form}
+use partman::{
+    Error, Library, Result, api::digikey, barcode, bom, cost, crossref, datasheet, jlc, kit, order, reorder, config::{Config, Issue, Secrets, Severity, StorageBackend, UserConfig}, data, editor::{Form, edit_file, process_form}, history,
+    part::Part, refresh, report, util::{self, format_timestamp, parse_duration},
 };
 
-use clap::{Parser, Subcommand};
+use crate::output::{self, Emit, OutputFormat};
+
+use clap::{builder::{PossibleValuesParser, TypedValueParser}, Parser, Subcommand};
+use serde::Serialize;
 
 #[derive(Parser, Debug)]
 #[command(name = "partman")]
 #[command(about = "KiCad DB lib part management", long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
-    pub command: Cmd
+    pub command: Cmd,
+
+    /// Output format, `json` prints a single JSON document on stdout.
+    /// Defaults to `output` in the user config, or `text`
+    #[arg(long, global = true, value_parser = output::parser())]
+    pub output: Option<OutputFormat>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -25,15 +34,72 @@ pub enum Cmd {
     /// Add a new part
     Add(AddCmd),
 
-    /// Resume the last part add
+    /// Resume the last part add, or a specific draft from history
     Resume(ResumeCmd),
+
+    /// Manage the draft history
+    #[command(subcommand)]
+    History(HistoryCmd),
+
+    /// Inspect the partman.toml config
+    #[command(subcommand)]
+    Config(ConfigCmd),
+
+    /// Refresh stock, price and lifecycle data from DigiKey
+    Refresh(RefreshCmd),
+
+    /// Generate reports about the library
+    #[command(subcommand)]
+    Report(ReportCmd),
+
+    /// JLCPCB assembly parts library integration
+    #[command(subcommand)]
+    Jlc(JlcCmd),
+
+    /// Fill empty second supplier part numbers by searching the part's MPN
+    Crossref(CrossrefCmd),
+
+    /// Manage the local datasheet archive
+    #[command(subcommand)]
+    Datasheets(DatasheetsCmd),
+
+    /// Check a KiCad schematic or netlist against the library and print its BOM
+    Bom(BomCmd),
+
+    /// Write supplier order upload files for building a design
+    Order(OrderCmd),
+
+    /// Cost of building a design from DigiKey price breaks
+    Cost(CostCmd),
+
+    /// Track stock on hand and where it is stored
+    #[command(subcommand)]
+    Stock(StockCmd),
+
+    /// Take the parts for building a design out of stock
+    Kit(KitCmd),
+
+    /// Move the library's parts to another storage backend
+    Convert(ConvertCmd),
+
+    /// Rewrite category CSV files in canonical form, sorted by ID
+    Fmt(FmtCmd),
 }
 
 #[derive(Parser, Debug)]
-pub struct InitCmd {}
+pub struct InitCmd {
+    /// Create the user config shared by every library instead of a project
+    #[arg(long)]
+    pub user: bool,
+}
 
 #[derive(Parser, Debug)]
-pub struct BuildCmd {}
+pub struct BuildCmd {
+    /// Delete the DB file and rebuild every table, instead of only tables
+    /// whose parts changed
+    #[arg(long)]
+    pub full: bool,
+}
 
 #[derive(Parser, Debug)]
 pub struct AddCmd {
@@ -41,96 +107,1619 @@ pub struct AddCmd {
     #[arg(short, long)]
     pub digikey: Option<String>,
 
+    /// Search DigiKey by keyword and pick a result to pre-fill from
+    #[arg(long, value_name = "KEYWORDS", conflicts_with_all = ["digikey", "from", "stdin"])]
+    pub digikey_search: Option<String>,
+
+    /// Pre-fill the second supplier from an LCSC part number (e.g. C2040) or
+    /// MPN, also fills empty component fields
+    #[arg(long, value_name = "CODE_OR_MPN")]
+    pub lcsc: Option<String>,
+
+    /// Pre-fill from a decoded DigiKey/Mouser bag label, `-` reads a scan from stdin
+    #[arg(long, value_name = "DECODED", conflicts_with_all = ["digikey", "digikey_search", "from", "stdin"])]
+    pub barcode: Option<String>,
+
     /// Don't build DB file after adding part
     #[arg(short, long)]
     pub no_build: bool,
+
+    /// Download the datasheet into the local archive, on by default with
+    /// `[datasheets] auto_download`
+    #[arg(long)]
+    pub download_datasheet: bool,
+
+    /// Set a form field, e.g. `--field value=100n` or `--field about.value=100n`
+    #[arg(short, long = "field", value_name = "KEY=VALUE", value_parser = parse_field)]
+    pub fields: Vec<(String, String)>,
+
+    /// Save the part without opening the editor, prints the new ID
+    #[arg(long)]
+    pub no_edit: bool,
+
+    /// Read a complete form from a TOML or JSON file instead of the editor
+    #[arg(long, conflicts_with_all = ["stdin", "digikey"])]
+    pub from: Option<PathBuf>,
+
+    /// Read a complete form as TOML or JSON from stdin instead of the editor
+    #[arg(long, conflicts_with = "digikey")]
+    pub stdin: bool,
+}
+
+fn parse_field(s: &str) -> core::result::Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.trim().to_owned(), v.to_owned()))
+        .ok_or_else(|| format!("Invalid field '{s}', expected KEY=VALUE"))
+}
+
+#[derive(Parser, Debug)]
+pub struct ResumeCmd {
+    /// Draft ID to resume, see `partman history list`. Defaults to the latest draft
+    pub id: Option<String>,
+
+    /// Resume a draft that was already saved, adding it again as a new part
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HistoryCmd {
+    /// List drafts in the history directory
+    List(HistoryListCmd),
+
+    /// Delete old drafts from the history directory
+    Prune(HistoryPruneCmd),
+}
+
+#[derive(Parser, Debug)]
+pub struct HistoryListCmd {}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCmd {
+    /// Report every problem found in partman.toml
+    Check(ConfigCheckCmd),
+}
+
+#[derive(Parser, Debug)]
+pub struct ConfigCheckCmd {}
+
+#[derive(Subcommand, Debug)]
+pub enum ReportCmd {
+    /// List discontinued, EOL, out of stock or unsourced parts, grouped by category
+    Lifecycle(ReportLifecycleCmd),
+}
+
+#[derive(Parser, Debug)]
+pub struct ReportLifecycleCmd {
+    /// Only report on parts in this category
+    #[arg(short, long)]
+    pub category: Option<String>,
+
+    /// Write the report as an HTML file instead of printing a table
+    #[arg(long, value_name = "FILE")]
+    pub html: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum JlcCmd {
+    /// Mark parts as JLC basic/extended from a downloaded JLCPCB parts library CSV
+    Import(JlcImportCmd),
+}
+
+#[derive(Parser, Debug)]
+pub struct JlcImportCmd {
+    /// JLCPCB parts library CSV
+    pub file: PathBuf,
+
+    /// Don't build DB file after importing
+    #[arg(short, long)]
+    pub no_build: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(group = clap::ArgGroup::new("target").required(true))]
+pub struct CrossrefCmd {
+    /// Cross-reference the part with this ID
+    #[arg(short, long, group = "target")]
+    pub id: Option<String>,
+
+    /// Cross-reference every part
+    #[arg(short, long, group = "target")]
+    pub all: bool,
+
+    /// Report matches without saving them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Don't build DB file after saving
+    #[arg(short, long)]
+    pub no_build: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct BomCmd {
+    /// KiCad schematic (.kicad_sch) or netlist (.xml or .net)
+    pub file: PathBuf,
+}
+
+#[derive(Parser, Debug)]
+pub struct OrderCmd {
+    /// KiCad XML netlist (schematics and .net netlists also work)
+    pub file: PathBuf,
+
+    /// Number of boards to build
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub qty: u32,
+
+    /// Directory to write the order files to
+    #[arg(short, long, default_value = ".")]
+    pub out_dir: PathBuf,
+}
+
+#[derive(Parser, Debug)]
+pub struct CostCmd {
+    /// KiCad XML netlist (schematics and .net netlists also work)
+    pub file: PathBuf,
+
+    /// Number of boards, repeat or separate with commas to quote several,
+    /// e.g. `--qty 10,100,1000`
+    #[arg(short, long, value_delimiter = ',', default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    pub qty: Vec<u32>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StockCmd {
+    /// Add stock of a part to a location
+    Add(StockAddCmd),
+
+    /// Remove used stock of a part
+    Take(StockTakeCmd),
+
+    /// Move stock of a part between locations
+    Move(StockMoveCmd),
+
+    /// Show stock of a part, or of every part
+    Show(StockShowCmd),
+
+    /// Set the minimum and target stock of a part
+    Levels(StockLevelsCmd),
+
+    /// List parts below their minimum stock and write supplier cart files
+    Reorder(StockReorderCmd),
+
+    /// Show the ledger of stock changes
+    Ledger(StockLedgerCmd),
+}
+
+#[derive(Parser, Debug)]
+pub struct StockAddCmd {
+    /// Part ID
+    pub id: String,
+
+    /// Quantity to add
+    #[arg(value_parser = clap::value_parser!(u32).range(1..))]
+    pub qty: u32,
+
+    /// Storage location, e.g. a drawer label
+    #[arg(short, long)]
+    pub location: String,
+
+    /// Lot, date code or reel notes
+    #[arg(long)]
+    pub lot: Option<String>,
+
+    /// Don't build DB file after updating stock
+    #[arg(short, long)]
+    pub no_build: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct StockTakeCmd {
+    /// Part ID
+    pub id: String,
+
+    /// Quantity to remove
+    #[arg(value_parser = clap::value_parser!(u32).range(1..))]
+    pub qty: u32,
+
+    /// Location to take from, needed when the part is stored in several
+    #[arg(short, long)]
+    pub location: Option<String>,
+
+    /// Don't build DB file after updating stock
+    #[arg(short, long)]
+    pub no_build: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct StockMoveCmd {
+    /// Part ID
+    pub id: String,
+
+    /// Location to move from
+    #[arg(long)]
+    pub from: String,
+
+    /// Location to move to
+    #[arg(long)]
+    pub to: String,
+
+    /// Quantity to move, defaults to everything in the source location
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub qty: Option<u32>,
+
+    /// Don't build DB file after updating stock
+    #[arg(short, long)]
+    pub no_build: bool,
 }
 
 #[derive(Parser, Debug)]
-pub struct ResumeCmd {}
+#[command(group = clap::ArgGroup::new("level").required(true))]
+pub struct StockLevelsCmd {
+    /// Part ID
+    pub id: String,
+
+    /// Reorder when stock on hand falls below this
+    #[arg(long, group = "level")]
+    pub min: Option<u32>,
+
+    /// Order up to this quantity, defaults to the minimum
+    #[arg(long, requires = "min")]
+    pub target: Option<u32>,
+
+    /// Stop tracking the part's stock level
+    #[arg(long, group = "level")]
+    pub clear: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct StockReorderCmd {
+    /// Directory to write the cart files to
+    #[arg(short, long, default_value = ".")]
+    pub out_dir: PathBuf,
+
+    /// Don't look up DigiKey minimum order quantities
+    #[arg(long)]
+    pub no_lookup: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct StockShowCmd {
+    /// Part ID, shows every part's stock if omitted
+    pub id: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct StockLedgerCmd {
+    /// Part ID, shows changes to every part if omitted
+    pub id: Option<String>,
+
+    /// Only show changes for kits built for this project
+    #[arg(short, long)]
+    pub project: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct ConvertCmd {
+    /// Backend to move the parts to
+    #[arg(long, value_parser = PossibleValuesParser::new(["csv", "sqlite"]).map(|s| match s.as_str() {
+        "sqlite" => StorageBackend::Sqlite,
+        _ => StorageBackend::Csv,
+    }))]
+    pub to: StorageBackend,
+
+    /// Replace parts already in the target backend
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct FmtCmd {
+    /// Only report files that aren't formatted, exiting with code 11 if
+    /// there are any
+    #[arg(long)]
+    pub check: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct KitCmd {
+    /// KiCad XML netlist (schematics and .net netlists also work)
+    pub file: PathBuf,
+
+    /// Number of boards to build
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub boards: u32,
+
+    /// Project name for the ledger, defaults to the file name
+    #[arg(short, long)]
+    pub project: Option<String>,
+
+    /// Take the stock without asking for confirmation, needed with `--output json`
+    #[arg(short, long, conflicts_with = "dry_run")]
+    pub yes: bool,
+
+    /// Report what would be taken without changing stock
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Don't build DB file after updating stock
+    #[arg(short, long)]
+    pub no_build: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DatasheetsCmd {
+    /// Download missing or changed datasheets into the datasheet directory
+    Sync(DatasheetsSyncCmd),
+}
+
+#[derive(Parser, Debug)]
+pub struct DatasheetsSyncCmd {
+    /// Only sync parts in this category
+    #[arg(short, long)]
+    pub category: Option<String>,
+
+    /// Only sync the part with this ID
+    #[arg(short, long)]
+    pub id: Option<String>,
+
+    /// Download again even if the local copy matches the recorded hash
+    #[arg(long)]
+    pub force: bool,
+
+    /// Don't build DB file after syncing
+    #[arg(short, long)]
+    pub no_build: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct RefreshCmd {
+    /// Only refresh parts in this category
+    #[arg(short, long)]
+    pub category: Option<String>,
+
+    /// Only refresh the part with this ID
+    #[arg(short, long)]
+    pub id: Option<String>,
+
+    /// Set Status to obsolete for parts DigiKey lists as discontinued or end of life
+    #[arg(long)]
+    pub mark_obsolete: bool,
+
+    /// Also download missing or changed datasheets, on by default with
+    /// `[datasheets] auto_download`
+    #[arg(long)]
+    pub datasheets: bool,
+
+    /// Don't build DB file after refreshing
+    #[arg(short, long)]
+    pub no_build: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct HistoryPruneCmd {
+    /// Delete drafts older than this, e.g. 30d, 12h, 2w
+    #[arg(long, value_parser = parse_duration)]
+    pub older_than: Duration,
+
+    /// Only delete drafts that were saved as parts
+    #[arg(long)]
+    pub committed: bool,
+}
+
+/// Number of DigiKey keyword search results offered to pick from
+const SEARCH_RESULTS: u32 = 10;
+
+/// Search DigiKey and let the user pick one of the results
+fn pick_digikey_product(client: &digikey::Client, keywords: &str) -> Result<digikey::DigikeyProduct> {
+    let mut products = client.search_keyword(keywords, SEARCH_RESULTS)?;
+    if products.is_empty() {
+        return Err(format!("No DigiKey results for '{keywords}'").into());
+    }
+
+    eprintln!("{:>3}  {:<24} {:<20} {:<40} {:>10} {:>10}", "#", "MPN", "Manufacturer", "Description", "Stock", "Price");
+    for (i, product) in products.iter().enumerate() {
+        eprintln!(
+            "{:>3}  {:<24} {:<20} {:<40} {:>10} {:>10}",
+            i + 1,
+            product.manufacturer_product_number,
+            product.manufacturer.name,
+            product.description.product_description,
+            product.quantity_available,
+            product.unit_price,
+        );
+    }
+
+    loop {
+        let answer = util::prompt(&format!("Pick a part [1-{}], or q to quit: ", products.len()))?;
+        if answer.eq_ignore_ascii_case("q") || answer.is_empty() {
+            return Err("No part picked".into());
+        }
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=products.len()).contains(&n) => return Ok(products.swap_remove(n - 1)),
+            _ => eprintln!("Invalid choice '{answer}'"),
+        }
+    }
+}
+
+/// Look up a scanned label on DigiKey, by supplier part number then MPN
+fn lookup_label(client: &digikey::Client, label: &barcode::Label) -> Result<digikey::DigikeyProduct> {
+    if let Some(spn) = &label.supplier_pn {
+        match client.get_product(spn) {
+            Ok(product) => return Ok(product),
+            // Not a DigiKey part number, e.g. a Mouser label, fall back to the MPN
+            Err(Error::Api { status: 404, .. }) if label.mpn.is_some() => {},
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mpn = label.mpn.as_deref().unwrap_or_default();
+    let mut product = client.get_product(mpn)?;
+    product.search_spn = product.preferred_spn().map(str::to_owned);
+    Ok(product)
+}
+
+/// Result of an add or resume, `None` fields when the form was not changed
+#[derive(Serialize)]
+struct AddOutput {
+    id: Option<String>,
+    part: Option<Part>,
+}
+
+/// Save an edited draft as a new part and report it
+/// Build the KiCad DB from parts changed in memory
+fn build_db(parts: Vec<Part>, config: &Config) -> Result<()> {
+    let report = data::build_kicad_db(parts, config)?;
+    eprintln!("Successfully built KiCad DB: {}", report.db_path.display());
+    Ok(())
+}
+
+fn save_draft(draft: &Path, edited: Option<Form>, library: &Library, download: bool, output: OutputFormat) -> Result<()> {
+    let part = match edited {
+        None => None,
+        Some(edited_form) => {
+            let part = process_form(edited_form, library, download)?;
+            history::mark_committed(draft, part.id.as_deref().unwrap_or_default())?;
+            Some(part)
+        },
+    };
+
+    let report = AddOutput { id: part.as_ref().and_then(|p| p.id.clone()), part };
+    output.emit(&report, || {
+        if report.part.is_none() {
+            println!("no changes!");
+        }
+    })
+}
 
 impl InitCmd {
-    pub fn run(&self) -> Result<()> {
+    pub fn run(&self, output: OutputFormat) -> Result<()> {
+        if self.user {
+            return self.run_user(output);
+        }
+
         let config_path = Path::new("partman.toml");
         if config_path.exists() {
             return Err("partman.toml already exists in this directory".into());
         }
-        fs::write(config_path, Config::DEFAULT_CONFIG)?;
-        println!("Created default partman.toml");
-
         let secrets_path = Path::new("secrets.toml");
         if secrets_path.exists() {
             return Err("secrets.toml already exists in this directory".into());
         }
+
+        fs::write(config_path, Config::DEFAULT_CONFIG)?;
         fs::write(secrets_path, Secrets::DEFAULT_SECRETS)?;
-        println!("Created default secrets.toml");
 
-        Ok(())
+        let created = [config_path, secrets_path];
+        output.emit(&created, || {
+            for path in created {
+                println!("Created default {}", path.display());
+            }
+        })
+    }
+}
+
+impl InitCmd {
+    fn run_user(&self, output: OutputFormat) -> Result<()> {
+        let path = UserConfig::path().ok_or("Could not determine the user config directory")?;
+        if path.exists() {
+            return Err(format!("{} already exists", path.display()).into());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, UserConfig::DEFAULT_USER_CONFIG)?;
+
+        output.emit(&[&path], || println!("Created default {}", path.display()))
     }
 }
 
 impl BuildCmd {
-    pub fn run(&self, config: &Config) -> Result<()> {
-        let parts = data::load_all_parts(&config.cat, &config.csv_dir_path)?;
-        data::build_kicad_db(&config.db_file_path, parts, &config.cat)?;
-        Ok(())
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let report = if self.full {
+            library.rebuild()?
+        } else {
+            library.build()?
+        };
+        eprintln!("Successfully built KiCad DB: {}", report.db_path.display());
+        output.emit(&report, || {
+            for (table, rows) in &report.tables {
+                let unchanged = if report.unchanged.contains(table) { ", unchanged" } else { "" };
+                println!("  {table:<16} {rows} rows{unchanged}");
+            }
+            for table in &report.removed {
+                println!("  {table:<16} removed");
+            }
+        })
     }
 }
 
 impl AddCmd {
-    pub fn run(&self, config: &Config) -> Result<()> {
-       
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let interactive = !(self.no_edit || self.stdin || self.from.is_some());
+        // Only the editor gets the sample part, anything else starts empty
+        let base = if interactive { Form::default() } else { Form::blank() };
+
+        let mut lcsc_product = match &self.lcsc {
+            Some(code) => Some(library.lcsc().lookup(code)?),
+            None => None,
+        };
+
         let mut form = match &self.digikey {
-            None => Form::default(),
-            Some(mpn) => {
-                // TODO: HAndle the case that secrets are empty strings!
-                let id = &config.digikey.as_ref().ok_or("digikey config missing")?.client_id;
-                let secret = &config.digikey.as_ref().ok_or("digikey config missing")?.client_secret;
-                digikey::get_product(id, secret, mpn)?.into()
+            None if self.barcode.is_some() => {
+                let scanned = match self.barcode.as_deref() {
+                    Some("-") => util::prompt("Scan barcode: ")?,
+                    other => other.unwrap_or_default().to_owned(),
+                };
+                let label = barcode::parse(&scanned)?;
+                let product = lookup_label(&library.digikey()?, &label)?;
+                let mut form = base.with_digikey(product);
+                if let Some(qty) = label.qty {
+                    let desc = form.long_description.take().unwrap_or_default();
+                    form.long_description = Some(format!("{desc} (scanned bag quantity: {qty})"));
+                }
+                form
+            },
+            None if self.digikey_search.is_some() => {
+                let keywords = self.digikey_search.as_deref().unwrap_or_default();
+                base.with_digikey(pick_digikey_product(&library.digikey()?, keywords)?)
+            },
+            None if self.stdin => {
+                let mut content = String::new();
+                io::stdin().read_to_string(&mut content)?;
+                Form::parse(&content)?
             },
+            None => match &self.from {
+                Some(path) => Form::parse(&fs::read_to_string(path)?)?,
+                None => match lcsc_product.take() {
+                    Some(product) => product.into(),
+                    None => base,
+                },
+            },
+            Some(spn) => base.with_digikey(library.digikey()?.get_product(spn)?),
         };
-        form.categories = Some(config.cat.clone());
-        let rslt = edit(&config.editor_cmd, &config.history_dir_path, form)?;
+        if let Some(product) = lcsc_product {
+            form.apply_lcsc(product);
+        }
+        for (key, value) in &self.fields {
+            form.set_field(key, value)?;
+        }
 
-        match rslt {
-            None => println!("no changes!"),
-            Some(edited_form) => process_form(edited_form, config)?,
+        let download = self.download_datasheet || config.datasheets.auto_download;
+        if !interactive {
+            let part = process_form(form, library, download)?;
+            let report = AddOutput { id: part.id.clone(), part: Some(part) };
+            // Only the ID goes to stdout so scripts can capture it
+            return output.emit(&report, || println!("{}", report.id.as_deref().unwrap_or_default()));
         }
 
-        Ok(())
+        form.categories = Some(config.cat.clone());
+        let draft = history::create(&config.history_dir_path, &form)?;
+        let rslt = edit_file(&config.editor_cmd, &draft.path)?;
+
+        save_draft(&draft.path, rslt, library, download, output)
     }
 }
 
 impl ResumeCmd {
-    pub fn run(&self, config: &Config) -> Result<()> {
-
-        let entries = fs::read_dir(&config.history_dir_path)?;
-
-        let latest_timestamp = entries
-            .flatten()
-            .filter(|ent| ent.path().extension() == Some(OsStr::new("toml")))
-            .filter_map(|ent| {
-                ent.path()
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .and_then(|s| s.parse::<u64>().ok())
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let draft = history::find(&config.history_dir_path, self.id.as_deref())?;
+
+        if let Some(part_id) = &draft.committed {
+            if !self.force {
+                return Err(Error::Validation(format!(
+                    "Draft {} was already saved as {part_id}, use --force to add it again", draft.id
+                )));
+            }
+            eprintln!("Note: draft {} was already saved as {}", draft.id, part_id);
+        }
+        eprintln!("Resuming from {}", &draft.path.display());
+
+        let rslt = edit_file(&config.editor_cmd, &draft.path)?;
+
+        save_draft(&draft.path, rslt, library, config.datasheets.auto_download, output)
+    }
+}
+
+impl HistoryCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        match self {
+            HistoryCmd::List(cmd) => cmd.run(library, output),
+            HistoryCmd::Prune(cmd) => cmd.run(library, output),
+        }
+    }
+}
+
+/// A draft as shown by `history list`
+#[derive(Serialize)]
+struct DraftSummary {
+    id: String,
+    path: PathBuf,
+    created: String,
+    timestamp: u64,
+    category: Option<String>,
+    description: Option<String>,
+    committed: Option<String>,
+}
+
+impl HistoryListCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let drafts: Vec<DraftSummary> = history::list(&config.history_dir_path)?
+            .into_iter()
+            .map(|draft| {
+                let form = draft.form();
+                DraftSummary {
+                    created: format_timestamp(draft.timestamp),
+                    category: form.as_ref().map(|f| f.about.category.clone()),
+                    description: form.map(|f| f.about.description),
+                    id: draft.id,
+                    path: draft.path,
+                    timestamp: draft.timestamp,
+                    committed: draft.committed,
+                }
             })
-            .max()
-            .ok_or("Error: Could not find temporary file to resume from")?;
+            .collect();
+
+        output.emit(&drafts, || {
+            if drafts.is_empty() {
+                println!("No drafts in {}", config.history_dir_path.display());
+                return;
+            }
+
+            println!("{:<14} {:<16} {:<12} {:<10} Description", "ID", "Created (UTC)", "Category", "Saved as");
+            for draft in &drafts {
+                println!(
+                    "{:<14} {:<16} {:<12} {:<10} {}",
+                    draft.id,
+                    draft.created,
+                    draft.category.as_deref().unwrap_or("?"),
+                    draft.committed.as_deref().unwrap_or("-"),
+                    draft.description.as_deref().unwrap_or("(invalid draft)"),
+                );
+            }
+        })
+    }
+}
 
-        let latest_file = config.history_dir_path
-            .join(latest_timestamp.to_string())
-            .with_extension("toml");
+impl HistoryPruneCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let pruned: Vec<PathBuf> = history::prune(&config.history_dir_path, self.older_than, self.committed)?
+            .into_iter()
+            .map(|draft| draft.path)
+            .collect();
 
-        println!("Resuming from {}", &latest_file.display());
-        
-        let rslt = edit_file(&config.editor_cmd, &latest_file)?;
+        output.emit(&pruned, || {
+            for path in &pruned {
+                println!("Removed {}", path.display());
+            }
+            println!("Pruned {} draft(s)", pruned.len());
+        })
+    }
+}
 
-        match rslt {
-            None => println!("no changes!"),
-            Some(edited_form) => process_form(edited_form, config)?,
+impl ConfigCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        match self {
+            ConfigCmd::Check(cmd) => cmd.run(library, output),
         }
+    }
+}
+
+#[derive(Serialize)]
+struct ConfigCheckOutput {
+    ok: bool,
+    issues: Vec<Issue>,
+}
+
+impl ConfigCheckCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let issues = config.validate();
+        let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+        let report = ConfigCheckOutput { ok: errors == 0, issues };
 
+        output.emit(&report, || {
+            for issue in &report.issues {
+                let label = match issue.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                };
+                println!("{label}: {}: {}", issue.key, issue.message);
+            }
+            if report.issues.is_empty() {
+                println!("partman.toml OK");
+            }
+        })?;
+
+        if errors > 0 {
+            return Err(Error::CheckFailed { problems: errors });
+        }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl RefreshCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let client = library.digikey()?;
+        let http = reqwest::blocking::Client::new();
+        let sync_datasheets = self.datasheets || config.datasheets.auto_download;
+        let mut parts = library.parts()?;
+        if let Some(id) = &self.id
+            && !parts.iter().any(|p| p.id.as_ref() == Some(id))
+        {
+            return Err(Error::Validation(format!("No part with ID '{id}'")));
+        }
+
+        let mut results = Vec::new();
+        for part in parts.iter_mut() {
+            if self.category.as_ref().is_some_and(|c| c != &part.category)
+                || self.id.as_ref().is_some_and(|id| Some(id) != part.id.as_ref())
+            {
+                continue;
+            }
+            if let Some(mut result) = refresh::refresh_part(&client, part, self.mark_obsolete) {
+                if output == OutputFormat::Text {
+                    eprintln!("  refreshed {} ({})", result.id, result.spn);
+                }
+                if sync_datasheets {
+                    result.datasheet = Some(datasheet::sync_part(&http, config, part, false).outcome);
+                }
+                results.push(result);
+            }
+        }
+
+        let mut categories: Vec<&str> = parts.iter()
+            .filter(|p| results.iter().any(|r| {
+                let downloaded = matches!(r.datasheet, Some(datasheet::Outcome::Downloaded { .. }));
+                (r.current.is_some() || downloaded) && p.id.as_ref() == Some(&r.id)
+            }))
+            .map(|p| p.category.as_str())
+            .collect();
+        categories.sort();
+        categories.dedup();
+        library.save_categories(&parts, &categories)?;
+
+        if !self.no_build && !categories.is_empty() {
+            build_db(parts, config)?;
+        }
+
+        output.emit(&results, || {
+            for result in &results {
+                if let Some(error) = &result.error {
+                    println!("{:<10} {:<24} error: {}", result.id, result.spn, error);
+                    continue;
+                }
+                let Some(current) = &result.current else { continue };
+                let mut flags = Vec::new();
+                if result.lifecycle_changed {
+                    flags.push(format!(
+                        "lifecycle changed from {}",
+                        result.previous.product_status.as_deref().unwrap_or("unknown")
+                    ));
+                }
+                if current.is_unavailable() {
+                    flags.push("discontinued/EOL".to_string());
+                }
+                if result.marked_obsolete {
+                    flags.push("marked obsolete".to_string());
+                }
+                match &result.datasheet {
+                    Some(datasheet::Outcome::Downloaded { .. }) => flags.push("datasheet downloaded".to_string()),
+                    Some(datasheet::Outcome::Error { message }) => flags.push(format!("datasheet error: {message}")),
+                    _ => {},
+                }
+                println!(
+                    "{:<10} {:<24} {:<14} qty {:<8} price {:<10} {}",
+                    result.id,
+                    result.spn,
+                    current.product_status.as_deref().unwrap_or("-"),
+                    current.qty_available.unwrap_or_default(),
+                    current.unit_price.unwrap_or_default(),
+                    flags.join(", "),
+                );
+            }
+            println!("Refreshed {} part(s)", results.iter().filter(|r| r.current.is_some()).count());
+        })
+    }
+}
+
+impl ReportCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        match self {
+            ReportCmd::Lifecycle(cmd) => cmd.run(library, output),
+        }
+    }
+}
+
+impl ReportLifecycleCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let client = library.digikey()?;
+        let parts: Vec<Part> = library.parts()?
+            .into_iter()
+            .filter(|p| self.category.as_ref().is_none_or(|c| c == &p.category))
+            .collect();
+
+        let report = report::lifecycle(&client, &parts, |id| {
+            if output == OutputFormat::Text {
+                eprintln!("  checking {id}");
+            }
+        });
+
+        if let Some(path) = &self.html {
+            fs::write(path, report::lifecycle_html(&report, "Lifecycle Report"))?;
+            if output == OutputFormat::Text {
+                eprintln!("Wrote {}", path.display());
+            }
+        }
+
+        output.emit(&report, || {
+            if self.html.is_some() {
+                return;
+            }
+            if report.is_empty() {
+                println!("No issues found");
+            }
+            for (category, lines) in &report {
+                println!("\n{category}");
+                for line in lines {
+                    let findings: Vec<String> = line.findings.iter().map(report::Finding::describe).collect();
+                    println!(
+                        "  {:<10} {:<24} {:<24} {}",
+                        line.id,
+                        line.mpn.as_deref().unwrap_or("-"),
+                        line.spn.as_deref().unwrap_or("-"),
+                        findings.join(", "),
+                    );
+                }
+            }
+        })
+    }
+}
+
+impl JlcCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        match self {
+            JlcCmd::Import(cmd) => cmd.run(library, output),
+        }
+    }
+}
+
+/// A part whose JLC library type was changed by an import
+#[derive(Serialize)]
+struct JlcChange {
+    id: String,
+    lcsc: String,
+    previous: Option<String>,
+    current: Option<String>,
+}
+
+impl JlcImportCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let mut parts = library.parts()?;
+        let wanted = parts.iter()
+            .filter_map(|p| p.lcsc_spn())
+            .map(|code| code.trim().to_uppercase())
+            .collect();
+        let types = jlc::load_types(&self.file, &wanted)?;
+
+        let mut changes = Vec::new();
+        let mut categories = Vec::new();
+        for part in parts.iter_mut() {
+            let Some(code) = part.lcsc_spn().map(|c| c.trim().to_uppercase()) else { continue };
+            let current = types.get(&code).cloned();
+            if current == part.jlc_type {
+                continue;
+            }
+            let previous = std::mem::replace(&mut part.jlc_type, current.clone());
+            changes.push(JlcChange {
+                id: part.id.clone().unwrap_or_default(),
+                lcsc: code,
+                previous,
+                current,
+            });
+            categories.push(part.category.clone());
+        }
+
+        categories.sort();
+        categories.dedup();
+        let categories: Vec<&str> = categories.iter().map(String::as_str).collect();
+        library.save_categories(&parts, &categories)?;
+        if !self.no_build && !categories.is_empty() {
+            build_db(parts, config)?;
+        }
+
+        output.emit(&changes, || {
+            for change in &changes {
+                println!(
+                    "{:<10} {:<10} {} -> {}",
+                    change.id,
+                    change.lcsc,
+                    change.previous.as_deref().unwrap_or("-"),
+                    change.current.as_deref().unwrap_or("not in JLC library"),
+                );
+            }
+            println!("Updated {} part(s)", changes.len());
+        })
+    }
+}
+
+impl CrossrefCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let backends = config.crossref.suppliers.iter()
+            .map(|name| crossref::Backend::from_name(name, config))
+            .collect::<Result<Vec<_>>>()?;
+        let mut parts = library.parts()?;
+
+        let mut results = Vec::new();
+        let mut categories = Vec::new();
+        for part in parts.iter_mut() {
+            if !self.all && self.id.as_ref() != part.id.as_ref() {
+                continue;
+            }
+            let result = crossref::crossref_part(&backends, part);
+            if matches!(result.outcome, crossref::Outcome::Filled { .. }) {
+                categories.push(part.category.clone());
+            }
+            results.push(result);
+        }
+        if let (Some(id), true) = (&self.id, results.is_empty()) {
+            return Err(Error::Validation(format!("No part with ID '{id}'")));
+        }
+
+        if !self.dry_run {
+            categories.sort();
+            categories.dedup();
+            let categories: Vec<&str> = categories.iter().map(String::as_str).collect();
+            library.save_categories(&parts, &categories)?;
+            if !self.no_build && !categories.is_empty() {
+                build_db(parts, config)?;
+            }
+        }
+
+        output.emit(&results, || {
+            for result in &results {
+                let mpn = result.mpn.as_deref().unwrap_or("-");
+                match &result.outcome {
+                    crossref::Outcome::Filled { candidate } => println!(
+                        "{:<10} {:<24} {} {}", result.id, mpn, candidate.supplier, candidate.spn
+                    ),
+                    crossref::Outcome::Ambiguous { candidates } => {
+                        println!("{:<10} {:<24} ambiguous, resolve manually:", result.id, mpn);
+                        for c in candidates {
+                            println!(
+                                "{:<36} {} {} ({})",
+                                "", c.supplier, c.spn, c.manufacturer.as_deref().unwrap_or("unknown manufacturer")
+                            );
+                        }
+                    },
+                    crossref::Outcome::NotFound => println!("{:<10} {:<24} not found", result.id, mpn),
+                    crossref::Outcome::Skipped { reason } => {
+                        if !self.all {
+                            println!("{:<10} {:<24} skipped, {reason}", result.id, mpn);
+                        }
+                    },
+                    crossref::Outcome::Error { message } => println!("{:<10} {:<24} error: {message}", result.id, mpn),
+                }
+            }
+            if self.dry_run {
+                println!("Dry run, no changes saved");
+            }
+        })
+    }
+}
+
+impl DatasheetsCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        match self {
+            DatasheetsCmd::Sync(cmd) => cmd.run(library, output),
+        }
+    }
+}
+
+impl DatasheetsSyncCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let http = reqwest::blocking::Client::new();
+        let mut parts = library.parts()?;
+
+        let mut results = Vec::new();
+        let mut categories = Vec::new();
+        for part in parts.iter_mut() {
+            if self.category.as_ref().is_some_and(|c| c != &part.category)
+                || self.id.as_ref().is_some_and(|id| Some(id) != part.id.as_ref())
+            {
+                continue;
+            }
+            if output == OutputFormat::Text && part.datasheet.is_some() {
+                eprintln!("  syncing {}", part.id.as_deref().unwrap_or_default());
+            }
+            let result = datasheet::sync_part(&http, config, part, self.force);
+            if matches!(result.outcome, datasheet::Outcome::Downloaded { .. }) {
+                categories.push(part.category.clone());
+            }
+            results.push(result);
+        }
+        if let (Some(id), true) = (&self.id, results.is_empty()) {
+            return Err(Error::Validation(format!("No part with ID '{id}'")));
+        }
+
+        categories.sort();
+        categories.dedup();
+        let categories: Vec<&str> = categories.iter().map(String::as_str).collect();
+        library.save_categories(&parts, &categories)?;
+        if !self.no_build && !categories.is_empty() {
+            build_db(parts, config)?;
+        }
+
+        output.emit(&results, || {
+            let mut downloaded = 0;
+            for result in &results {
+                match &result.outcome {
+                    datasheet::Outcome::Downloaded { file, bytes, .. } => {
+                        downloaded += 1;
+                        println!("{:<10} downloaded {file} ({bytes} bytes)", result.id);
+                    },
+                    datasheet::Outcome::Error { message } => println!("{:<10} error: {message}", result.id),
+                    datasheet::Outcome::UpToDate { .. } | datasheet::Outcome::NoUrl => {},
+                }
+            }
+            let count = |f: fn(&datasheet::Outcome) -> bool| results.iter().filter(|r| f(&r.outcome)).count();
+            let up_to_date = count(|o| matches!(o, datasheet::Outcome::UpToDate { .. }));
+            let missing = count(|o| matches!(o, datasheet::Outcome::NoUrl));
+            println!("Downloaded {downloaded} datasheet(s), {up_to_date} up to date, {missing} part(s) have no datasheet URL");
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct BomOutput {
+    ok: bool,
+    components: usize,
+    lines: Vec<bom::BomLine>,
+}
+
+impl BomCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let parts = library.parts()?;
+        let components = bom::load_components(&self.file)?;
+        let lines = bom::build(&components, &parts);
+        let problems: usize = lines.iter().map(|l| l.problems.len()).sum();
+        let report = BomOutput { ok: problems == 0, components: components.len(), lines };
+
+        output.emit(&report, || {
+            println!("{:>4}  {:<10} {:<24} {:<24} {:<12} References", "Qty", "ID", "MPN", "Value", "Status");
+            for line in &report.lines {
+                println!(
+                    "{:>4}  {:<10} {:<24} {:<24} {:<12} {}",
+                    line.qty,
+                    line.id.as_deref().unwrap_or("-"),
+                    line.mpn.as_deref().unwrap_or("-"),
+                    line.value,
+                    line.status.as_deref().unwrap_or("-"),
+                    line.references.join(", "),
+                );
+            }
+
+            let problem_lines: Vec<&bom::BomLine> = report.lines.iter().filter(|l| !l.problems.is_empty()).collect();
+            if !problem_lines.is_empty() {
+                println!();
+                println!("Problems:");
+            }
+            for line in problem_lines {
+                let problems: Vec<&str> = line.problems.iter().map(bom::Problem::describe).collect();
+                let name = line.id.clone().unwrap_or_else(|| format!("{} {}", line.lib_id, line.value));
+                println!("  {:<32} {} ({})", name, problems.join(", "), line.references.join(", "));
+            }
+            println!();
+            println!("{} component(s), {} line(s)", report.components, report.lines.len());
+        })?;
+
+        if problems > 0 {
+            return Err(Error::CheckFailed { problems });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct OrderOutput {
+    boards: u32,
+    files: Vec<PathBuf>,
+    #[serde(flatten)]
+    plan: order::OrderPlan,
+}
+
+impl OrderCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let preferred = order::Supplier::preferred(config);
+
+        let parts = library.parts()?;
+        let components = bom::load_components(&self.file)?;
+        let lines = bom::build(&components, &parts);
+        let plan = order::plan(&lines, &preferred, self.qty);
+
+        let stem = self.file.file_stem().and_then(|s| s.to_str()).unwrap_or("order");
+        fs::create_dir_all(&self.out_dir)?;
+        let mut files = Vec::new();
+        for (supplier, order_lines) in &plan.orders {
+            files.push(order::write_file(&self.out_dir, stem, *supplier, order_lines)?);
+        }
+        files.extend(order::write_jlc_bom(&self.out_dir, stem, &lines)?);
+
+        let report = OrderOutput { boards: self.qty, files, plan };
+        output.emit(&report, || {
+            for (supplier, order_lines) in &report.plan.orders {
+                let total: usize = order_lines.iter().map(|l| l.qty).sum();
+                println!("{:<10} {} line(s), {} part(s)", supplier.name(), order_lines.len(), total);
+            }
+            for path in &report.files {
+                println!("Wrote {}", path.display());
+            }
+            if !report.plan.unassigned.is_empty() {
+                println!();
+                println!("Not assigned to a supplier:");
+            }
+            for line in &report.plan.unassigned {
+                let name = line.id.clone().unwrap_or_else(|| format!("{} {}", line.lib_id, line.value));
+                println!("  {:<32} x{:<5} {} ({})", name, line.qty, line.reason, line.references.join(", "));
+            }
+        })
+    }
+}
+
+impl CostCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let client = library.digikey()?;
+        let parts = library.parts()?;
+        let components = bom::load_components(&self.file)?;
+        let lines = bom::build(&components, &parts);
+
+        // Prices don't depend on the quantity, look each part up once
+        let mut pricing = HashMap::new();
+        for line in &lines {
+            let (Some(id), Some(spn)) = (&line.id, line.part.as_ref().and_then(Part::digikey_spn)) else {
+                continue;
+            };
+            if output == OutputFormat::Text {
+                eprintln!("  pricing {id} ({spn})");
+            }
+            pricing.insert(id.clone(), cost::fetch_pricing(&client, spn));
+        }
+
+        let currency = config.digikey_settings.currency();
+        let rollups: Vec<cost::Rollup> = self.qty.iter()
+            .map(|&boards| cost::rollup(&lines, &pricing, boards, currency))
+            .collect();
+
+        output.emit(&rollups, || {
+            for rollup in &rollups {
+                println!("{} board(s):", rollup.boards);
+                println!(
+                    "  {:<10} {:<24} {:>7} {:>7} {:>10} {:>10}  References",
+                    "ID", "SPN", "Needed", "Order", "Unit", "Extended"
+                );
+                for line in &rollup.lines {
+                    println!(
+                        "  {:<10} {:<24} {:>7} {:>7} {:>10.4} {:>10.2}  {}",
+                        line.id, line.spn, line.needed, line.order_qty, line.unit_price, line.extended,
+                        line.references.join(", "),
+                    );
+                }
+                for line in &rollup.unpriced {
+                    let name = line.id.clone().unwrap_or_else(|| format!("{} {}", line.lib_id, line.value));
+                    println!("  {:<35} not priced, {}", name, line.reason);
+                }
+                println!(
+                    "  Total {:.2} {}, {:.2} {} per board",
+                    rollup.total, rollup.currency, rollup.per_board, rollup.currency
+                );
+                println!();
+            }
+        })
+    }
+}
+
+impl StockCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        match self {
+            StockCmd::Add(cmd) => cmd.run(library, output),
+            StockCmd::Take(cmd) => cmd.run(library, output),
+            StockCmd::Move(cmd) => cmd.run(library, output),
+            StockCmd::Show(cmd) => cmd.run(library, output),
+            StockCmd::Levels(cmd) => cmd.run(library, output),
+            StockCmd::Reorder(cmd) => cmd.run(library, output),
+            StockCmd::Ledger(cmd) => cmd.run(library, output),
+        }
+    }
+}
+
+/// Save the inventory, record the changes in the ledger and rebuild the DB
+/// if it shows stock
+fn save_stock(
+    entries: &[data::StockEntry],
+    changes: &[data::LedgerEntry],
+    library: &Library,
+    no_build: bool,
+) -> Result<()> {
+    let config = library.config();
+    data::save_inventory(entries, &config.inventory.file_path)?;
+    data::append_ledger(changes, &config.inventory.ledger_path)?;
+    if config.inventory.db_columns && !no_build {
+        build_db(library.parts()?, config)?;
+    }
+    Ok(())
+}
+
+/// Print stock entries of one part, or every part
+fn emit_stock(entries: &[data::StockEntry], id: Option<&str>, output: OutputFormat) -> Result<()> {
+    let entries: Vec<&data::StockEntry> = entries.iter()
+        .filter(|e| e.qty > 0 && id.is_none_or(|id| e.id == id))
+        .collect();
+    output.emit(&entries, || {
+        if entries.is_empty() {
+            println!("No stock");
+            return;
+        }
+        for entry in &entries {
+            println!("{:<10} {:<16} {:>8}  {}", entry.id, entry.location, entry.qty, entry.lot);
+        }
+        if id.is_some() && entries.len() > 1 {
+            println!("{:<10} {:<16} {:>8}", "", "total", entries.iter().map(|e| e.qty).sum::<u32>());
+        }
+    })
+}
+
+impl StockAddCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        library.part(&self.id)?;
+
+        let mut entries = data::load_inventory(&config.inventory.file_path)?;
+        let time = format_timestamp(util::unix_now());
+        let change = data::add_stock(&mut entries, &self.id, &self.location, self.qty, self.lot.as_deref(), &time)?;
+        save_stock(&entries, &[change], library, self.no_build)?;
+        emit_stock(&entries, Some(&self.id), output)
+    }
+}
+
+impl StockTakeCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let mut entries = data::load_inventory(&config.inventory.file_path)?;
+        let time = format_timestamp(util::unix_now());
+        let change = data::take_stock(&mut entries, &self.id, self.location.as_deref(), self.qty, &time)?;
+        save_stock(&entries, &[change], library, self.no_build)?;
+        emit_stock(&entries, Some(&self.id), output)
+    }
+}
+
+impl StockMoveCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let mut entries = data::load_inventory(&config.inventory.file_path)?;
+        let time = format_timestamp(util::unix_now());
+        let changes = data::move_stock(&mut entries, &self.id, &self.from, &self.to, self.qty, &time)?;
+        save_stock(&entries, &changes, library, self.no_build)?;
+        emit_stock(&entries, Some(&self.id), output)
+    }
+}
+
+impl StockShowCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let entries = data::load_inventory(&config.inventory.file_path)?;
+        emit_stock(&entries, self.id.as_deref(), output)
+    }
+}
+
+impl StockLedgerCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let mut ledger = data::load_ledger(&config.inventory.ledger_path)?;
+        ledger.retain(|e| {
+            self.id.as_ref().is_none_or(|id| &e.id == id)
+                && self.project.as_ref().is_none_or(|p| &e.project == p)
+        });
+
+        output.emit(&ledger, || {
+            if ledger.is_empty() {
+                println!("No stock changes");
+                return;
+            }
+            for entry in &ledger {
+                println!(
+                    "{}  {:<10} {:<16} {:>+8}  {:<5} {}",
+                    entry.time, entry.id, entry.location, entry.change, entry.action, entry.project
+                );
+            }
+        })
+    }
+}
+
+impl StockLevelsCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let path = config.csv_dir_path.join(data::LEVELS_FILE);
+        let mut levels = data::load_levels(&path)?;
+        levels.retain(|l| l.id != self.id);
+
+        let level = match self.min {
+            Some(min) => {
+                library.part(&self.id)?;
+                let target = self.target.unwrap_or(min);
+                if target < min {
+                    return Err(Error::Validation(format!("Target {target} is below the minimum {min}")));
+                }
+                let level = data::StockLevel { id: self.id.clone(), min, target };
+                levels.push(level.clone());
+                Some(level)
+            },
+            None => None,
+        };
+        data::save_levels(&levels, &path)?;
+
+        output.emit(&level, || match &level {
+            Some(level) => println!("{} min {} target {}", level.id, level.min, level.target),
+            None => println!("{} stock level cleared", self.id),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct ReorderOutput {
+    lines: Vec<reorder::ReorderLine>,
+    files: Vec<PathBuf>,
+}
+
+impl StockReorderCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let preferred = order::Supplier::preferred(config);
+        let parts = library.parts()?;
+        let levels = data::load_levels(&config.csv_dir_path.join(data::LEVELS_FILE))?;
+        let stock = data::stock_by_id(&data::load_inventory(&config.inventory.file_path)?)?;
+
+        let client = match self.no_lookup {
+            true => None,
+            false => Some(library.digikey()?),
+        };
+        let lines = reorder::reorder(&levels, &stock, &parts, &preferred, client.as_ref());
+
+        let mut files = Vec::new();
+        let carts = reorder::cart(&lines);
+        if !carts.is_empty() {
+            fs::create_dir_all(&self.out_dir)?;
+        }
+        for (supplier, cart_lines) in &carts {
+            files.push(order::write_file(&self.out_dir, "reorder", *supplier, cart_lines)?);
+        }
+
+        let report = ReorderOutput { lines, files };
+        output.emit(&report, || {
+            if report.lines.is_empty() {
+                println!("Nothing below its minimum stock");
+                return;
+            }
+            println!(
+                "{:<10} {:>7} {:>7} {:>7} {:>7}  {:<10} {:<24} Note",
+                "ID", "OnHand", "Min", "Target", "Order", "Supplier", "SPN"
+            );
+            for line in &report.lines {
+                println!(
+                    "{:<10} {:>7} {:>7} {:>7} {:>7}  {:<10} {:<24} {}",
+                    line.id,
+                    line.on_hand,
+                    line.min,
+                    line.target,
+                    line.order_qty,
+                    line.supplier.map(order::Supplier::name).unwrap_or("-"),
+                    line.spn.as_deref().unwrap_or("-"),
+                    line.note.as_deref().unwrap_or_default(),
+                );
+            }
+            for path in &report.files {
+                println!("Wrote {}", path.display());
+            }
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct KitOutput {
+    #[serde(flatten)]
+    kit: kit::Kit,
+    /// Whether the stock was taken
+    taken: bool,
+}
+
+impl KitCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let parts = library.parts()?;
+        let components = bom::load_components(&self.file)?;
+        let lines = bom::build(&components, &parts);
+        let mut entries = data::load_inventory(&config.inventory.file_path)?;
+
+        let project = match &self.project {
+            Some(project) => project.clone(),
+            None => self.file.file_stem().and_then(|s| s.to_str()).unwrap_or("kit").to_owned(),
+        };
+        let kit = kit::plan(&lines, &entries, &project, self.boards);
+        let problems = kit.problems();
+
+        // Show the kit before asking. JSON is only printed once done, so
+        // there would be nothing to confirm against.
+        let ask = problems == 0 && !self.dry_run && !self.yes;
+        if ask && output.is_json() {
+            return Err(Error::Validation(
+                "With --output json pass --yes to take the stock or --dry-run to only show the kit".into()
+            ));
+        }
+        if ask {
+            print_kit(&kit);
+        }
+        let taken = problems == 0 && !self.dry_run && (self.yes || {
+            let answer = util::prompt(&format!(
+                "Take stock for {} board(s) of {}? [y/N] ", self.boards, kit.project
+            ))?;
+            answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")
+        });
+        if taken {
+            let changes = kit::apply(&kit, &mut entries, &format_timestamp(util::unix_now()));
+            save_stock(&entries, &changes, library, self.no_build)?;
+        }
+
+        let report = KitOutput { kit, taken };
+        output.emit(&report, || {
+            if !ask {
+                print_kit(&report.kit);
+            }
+            println!();
+            match (report.taken, problems) {
+                (true, _) => println!("Took stock for {} board(s) of {}", report.kit.boards, report.kit.project),
+                (false, 0) if self.dry_run => println!("Dry run, no stock taken"),
+                (false, 0) => println!("Cancelled, no stock taken"),
+                (false, _) => println!("{problems} problem(s), no stock taken"),
+            }
+        })?;
+
+        if problems > 0 {
+            return Err(Error::CheckFailed { problems });
+        }
+        Ok(())
+    }
+}
+
+/// Print what a kit takes from where, and anything missing
+fn print_kit(kit: &kit::Kit) {
+    println!("{:<10} {:>7} {:>7} {:>7}  From", "ID", "Needed", "OnHand", "Short");
+    for line in &kit.lines {
+        let takes: Vec<String> = line.takes.iter().map(|t| format!("{} {}", t.location, t.qty)).collect();
+        println!(
+            "{:<10} {:>7} {:>7} {:>7}  {}",
+            line.id, line.needed, line.on_hand, line.short, takes.join(", ")
+        );
+    }
+    if !kit.unmatched.is_empty() {
+        println!();
+        println!("Not in library:");
+    }
+    for line in &kit.unmatched {
+        println!("  {:<32} {:>5}  {}", format!("{} {}", line.lib_id, line.value), line.needed, line.references.join(", "));
+    }
+}
+
+#[derive(Serialize)]
+struct ConvertOutput {
+    from: StorageBackend,
+    to: StorageBackend,
+    parts: usize,
+}
+
+impl ConvertCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        let from = config.storage.backend;
+        if from == self.to {
+            return Err(Error::Validation(format!("The library already uses the {} backend", self.to.name())));
+        }
+
+        let parts = library.parts()?;
+        let target = data::open_backend(config, self.to)?;
+        let existing = target.load()?.len();
+        if existing > 0 && !self.force {
+            return Err(Error::Validation(format!(
+                "The {} backend already holds {existing} part(s), use --force to replace them",
+                self.to.name()
+            )));
+        }
+
+        let categories: Vec<&str> = config.cat.keys().map(String::as_str).collect();
+        target.save_categories(&parts, &categories)?;
+        let saved = target.load()?.len();
+        if saved != parts.len() {
+            return Err(format!("Only {saved} of {} parts were saved, partman.toml left unchanged", parts.len()).into());
+        }
+        if let Err(e) = config.save_storage_backend(self.to) {
+            eprintln!("{saved} part(s) were copied to the {} backend but partman.toml was left unchanged", self.to.name());
+            return Err(e);
+        }
+
+        let report = ConvertOutput { from, to: self.to, parts: saved };
+        output.emit(&report, || {
+            println!("Moved {} part(s) from {} to {}", report.parts, from.name(), self.to.name());
+            let old = match from {
+                StorageBackend::Csv => config.csv_dir_path.display(),
+                StorageBackend::Sqlite => config.storage.sqlite_path.display(),
+            };
+            println!("The {} store in {old} was left in place, delete it once you've checked the new one", from.name());
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct FmtOutput {
+    check: bool,
+    /// Files that were reformatted, or would be with `--check`
+    files: Vec<PathBuf>,
+}
+
+impl FmtCmd {
+    pub fn run(&self, library: &Library, output: OutputFormat) -> Result<()> {
+        let config = library.config();
+        if config.storage.backend != StorageBackend::Csv {
+            return Err(Error::Validation(format!(
+                "fmt only formats CSV files, this library uses the {} backend",
+                config.storage.backend.name()
+            )));
+        }
+
+        let files = data::CsvStore::new(config).format(self.check)?;
+        let report = FmtOutput { check: self.check, files };
+        output.emit(&report, || {
+            for path in &report.files {
+                match self.check {
+                    true => println!("Not formatted: {}", path.display()),
+                    false => println!("Formatted {}", path.display()),
+                }
+            }
+            if report.files.is_empty() {
+                println!("All category files are formatted");
+            }
+        })?;
+
+        if self.check && !report.files.is_empty() {
+            return Err(Error::CheckFailed { problems: report.files.len() });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,805 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
+use crate::{Error, Result};
+
+mod validate;
+mod user;
+
+pub use validate::{Issue, Severity};
+pub use user::{OutputFormat, UserConfig};
+use user::env_var;
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct Digikey {
+    #[serde(default)]
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: String,
+}
+
+/// DigiKey API settings from the `[digikey]` section of partman.toml
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct DigikeySettings {
+    /// Locale site, e.g. "AU", "US", "DE"
+    pub site: Option<String>,
+    /// Locale language, e.g. "en"
+    pub language: Option<String>,
+    /// Currency prices are returned in, e.g. "AUD", "USD"
+    pub currency: Option<String>,
+    /// Customer ID for account specific pricing
+    pub customer_id: Option<String>,
+    /// Use the DigiKey sandbox API instead of production
+    pub sandbox: Option<bool>,
+    /// Override the API base URL, e.g. for a local mock server
+    pub base_url: Option<String>,
+}
+
+impl DigikeySettings {
+    const PRODUCTION_URL: &str = "https://api.digikey.com";
+    const SANDBOX_URL: &str = "https://sandbox-api.digikey.com";
+
+    pub fn site(&self) -> &str {
+        self.site.as_deref().unwrap_or("AU")
+    }
+
+    pub fn language(&self) -> &str {
+        self.language.as_deref().unwrap_or("en")
+    }
+
+    pub fn currency(&self) -> &str {
+        self.currency.as_deref().unwrap_or("AUD")
+    }
+
+    /// API base URL without a trailing slash
+    pub fn base_url(&self) -> &str {
+        match &self.base_url {
+            Some(url) => url.trim_end_matches('/'),
+            None if self.sandbox == Some(true) => Self::SANDBOX_URL,
+            None => Self::PRODUCTION_URL,
+        }
+    }
+
+    /// Fill any unset fields from `other`
+    fn or(self, other: Self) -> Self {
+        Self {
+            site: self.site.or(other.site),
+            language: self.language.or(other.language),
+            currency: self.currency.or(other.currency),
+            customer_id: self.customer_id.or(other.customer_id),
+            sandbox: self.sandbox.or(other.sandbox),
+            base_url: self.base_url.or(other.base_url),
+        }
+    }
+}
+
+/// LCSC catalog settings from the `[lcsc]` section of partman.toml
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct LcscSettings {
+    /// Override the catalog base URL, e.g. for a local mock server
+    pub base_url: Option<String>,
+}
+
+impl LcscSettings {
+    const DEFAULT_URL: &str = "https://wmsc.lcsc.com";
+
+    /// Catalog base URL without a trailing slash
+    pub fn base_url(&self) -> &str {
+        self.base_url.as_deref()
+            .map(|url| url.trim_end_matches('/'))
+            .unwrap_or(Self::DEFAULT_URL)
+    }
+}
+
+/// Settings for `partman crossref` from the `[crossref]` section
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CrossrefSettings {
+    /// Secondary suppliers searched in order, "LCSC" and "DigiKey" are supported
+    pub suppliers: Vec<String>,
+}
+
+impl Default for CrossrefSettings {
+    fn default() -> Self {
+        Self { suppliers: vec![String::from("LCSC")] }
+    }
+}
+
+/// Settings for `partman order` from the `[order]` section
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OrderSettings {
+    /// Suppliers to order from in order of preference, "Digi-Key", "Mouser"
+    /// and "LCSC" are supported
+    pub preferred_suppliers: Vec<String>,
+}
+
+impl Default for OrderSettings {
+    fn default() -> Self {
+        Self {
+            preferred_suppliers: vec![String::from("Digi-Key"), String::from("Mouser"), String::from("LCSC")],
+        }
+    }
+}
+
+/// Inventory settings from the `[inventory]` section
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct InventorySettings {
+    /// CSV file with stock per part and location
+    pub file_path: PathBuf,
+    /// CSV file every stock change is appended to
+    pub ledger_path: PathBuf,
+    /// Add StockQty and StockLocation columns to the KiCad DB tables
+    pub db_columns: bool,
+}
+
+impl Default for InventorySettings {
+    fn default() -> Self {
+        Self {
+            file_path: PathBuf::from("inventory.csv"),
+            ledger_path: PathBuf::from("ledger.csv"),
+            db_columns: false,
+        }
+    }
+}
+
+/// What the KiCad DB Datasheet column links to
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DatasheetLink {
+    /// The original vendor URL
+    #[default]
+    Url,
+    /// The downloaded copy, falling back to the URL if not downloaded
+    Local,
+}
+
+/// Datasheet archive settings from the `[datasheets]` section
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct DatasheetSettings {
+    /// Directory datasheets are downloaded into, named by part ID
+    pub dir_path: PathBuf,
+    /// Download datasheets when adding or refreshing parts
+    pub auto_download: bool,
+    pub db_link: DatasheetLink,
+}
+
+impl Default for DatasheetSettings {
+    fn default() -> Self {
+        Self {
+            dir_path: PathBuf::from("datasheets/"),
+            auto_download: false,
+            db_link: DatasheetLink::Url,
+        }
+    }
+}
+
+/// Where a library's parts are kept
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// One CSV file per category in `csv_dir_path`
+    #[default]
+    Csv,
+    /// A single SQLite database at `[storage] sqlite_path`
+    Sqlite,
+}
+
+impl StorageBackend {
+    pub fn name(self) -> &'static str {
+        match self {
+            StorageBackend::Csv => "csv",
+            StorageBackend::Sqlite => "sqlite",
+        }
+    }
+}
+
+/// Part storage settings from the `[storage]` section
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct StorageSettings {
+    pub backend: StorageBackend,
+    /// Database holding every part when the backend is `sqlite`
+    pub sqlite_path: PathBuf,
+}
+
+impl Default for StorageSettings {
+    fn default() -> Self {
+        Self {
+            backend: StorageBackend::Csv,
+            sqlite_path: PathBuf::from("parts.sqlite"),
+        }
+    }
+}
+
+/// Extra objects in the generated DB for tools other than KiCad, from the
+/// `[db]` section. All off by default so the DB only holds the KiCad tables
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct DbSettings {
+    /// `parts_fts` FTS5 table over description, value, MPN, manufacturer and notes
+    pub search_index: bool,
+    /// `all_parts` view of every category table with a `category` column
+    pub all_parts_view: bool,
+    /// Indexes on the MPN and SPN columns of every category table
+    pub indexes: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Secrets {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digikey: Option<Digikey>,
+}
+
+impl Secrets {
+    pub const DEFAULT_SECRETS: &str = r#"
+# Partman API Keys File
+
+# Digikey API credentials, get them from https://developer.digikey.com/
+[digikey]
+client_id = ""
+client_secret = ""
+"#;
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Cat {
+    pub code: String,
+    pub filename: String,
+    pub subcategories: Vec<String>
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Config {
+    /// Directory holding partman.toml
+    #[serde(skip)]
+    pub root: PathBuf,
+    #[serde(default)]
+    pub editor_cmd: String,
+    pub db_file_path: PathBuf,
+    pub csv_dir_path: PathBuf,
+    pub history_dir_path: PathBuf,
+    #[serde(skip)]
+    pub digikey: Option<Digikey>,
+    #[serde(default, rename = "digikey")]
+    pub digikey_settings: DigikeySettings,
+    #[serde(default, rename = "lcsc")]
+    pub lcsc_settings: LcscSettings,
+    #[serde(default)]
+    pub crossref: CrossrefSettings,
+    #[serde(default)]
+    pub order: OrderSettings,
+    #[serde(default)]
+    pub inventory: InventorySettings,
+    #[serde(default)]
+    pub datasheets: DatasheetSettings,
+    #[serde(default)]
+    pub db: DbSettings,
+    #[serde(default)]
+    pub storage: StorageSettings,
+    pub cat: HashMap<String, Cat>
+}
+
+impl Config {
+    const CONFIG_NAME: &str = "partman.toml";
+    const SECRETS_NAME: &str = "secrets.toml";
+
+    /// Find and load the config, failing if validation finds any errors
+    pub fn find_and_load() -> Result<Self> {
+        Self::load(&Self::find_root()?)
+    }
+
+    /// Find and load the config without validating it
+    pub fn find_and_load_unchecked() -> Result<Self> {
+        Self::load_unchecked(&Self::find_root()?)
+    }
+
+    /// Load the config of the library in `root`, failing if validation finds
+    /// any errors
+    pub fn load(root: &Path) -> Result<Self> {
+        let config = Self::load_unchecked(root)?;
+
+        let errors: Vec<String> = config.validate()
+            .into_iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .map(|issue| format!("{}: {}", issue.key, issue.message))
+            .collect();
+        if !errors.is_empty() {
+            return Err(Error::ConfigInvalid(errors));
+        }
+
+        Ok(config)
+    }
+
+    /// The nearest directory holding partman.toml, starting from the
+    /// current directory
+    fn find_root() -> Result<PathBuf> {
+        let start = std::env::current_dir()?;
+        let mut current = start.clone();
+
+        loop {
+            let path = current.join(Self::CONFIG_NAME);
+            if path.exists() {
+                return Ok(current);
+            }
+
+            match current.parent() {
+                Some(parent) => current = parent.to_path_buf(),
+                None => return Err(Error::ConfigNotFound { dir: start, searched_parents: true }),
+            }
+        }
+    }
+
+    /// Load the config of the library in `root` without validating it
+    pub fn load_unchecked(root: &Path) -> Result<Self> {
+        let config_path = root.join(Self::CONFIG_NAME);
+        if !config_path.exists() {
+            return Err(Error::ConfigNotFound { dir: root.to_path_buf(), searched_parents: false });
+        }
+        let conf_str = fs::read_to_string(&config_path)?;
+        let mut config: Self = toml::from_str(&conf_str)
+            .map_err(|e| Error::config_parse(&config_path, &conf_str, e))?;
+
+        let secrets_path = root.join(Self::SECRETS_NAME);
+        if secrets_path.exists() {
+            let secrets_str = fs::read_to_string(&secrets_path)?;
+            if !secrets_str.trim().is_empty() {
+                let secrets: Secrets = toml::from_str(&secrets_str)
+                    .map_err(|e| Error::config_parse(&secrets_path, &secrets_str, e))?;
+                config.digikey = secrets.digikey;
+            }
+        }
+
+        config.apply_overrides(UserConfig::load()?, env_var);
+        config.root = root.to_path_buf();
+        
+        // Allow for paths relative to partman.toml
+        if config.csv_dir_path.is_relative() {
+            config.csv_dir_path = root.join(config.csv_dir_path.clone());
+        };
+
+        // Allow for paths relative to partman.toml
+        if config.db_file_path.is_relative() {
+            config.db_file_path = root.join(config.db_file_path.clone());
+        };
+
+        // Allow for paths relative to partman.toml
+        if config.history_dir_path.is_relative() {
+            config.history_dir_path = root.join(config.history_dir_path.clone());
+        };
+
+        // Allow for paths relative to partman.toml
+        if config.datasheets.dir_path.is_relative() {
+            config.datasheets.dir_path = root.join(config.datasheets.dir_path.clone());
+        };
+
+        // Allow for paths relative to partman.toml
+        if config.inventory.file_path.is_relative() {
+            config.inventory.file_path = root.join(config.inventory.file_path.clone());
+        }
+        if config.inventory.ledger_path.is_relative() {
+            config.inventory.ledger_path = root.join(config.inventory.ledger_path.clone());
+        };
+
+        // Allow for paths relative to partman.toml
+        if config.storage.sqlite_path.is_relative() {
+            config.storage.sqlite_path = root.join(config.storage.sqlite_path.clone());
+        };
+
+
+        Ok(config)
+    }
+
+    /// Merge the user config and environment variables into the project config
+    ///
+    /// The editor comes from `PARTMAN_EDITOR`, partman.toml, the user config,
+    /// then `$VISUAL` and `$EDITOR`. Each DigiKey credential comes from
+    /// `DIGIKEY_CLIENT_ID`/`DIGIKEY_CLIENT_SECRET`, secrets.toml, then the
+    /// user config. DigiKey settings come from partman.toml then the user
+    /// config, with `DIGIKEY_BASE_URL` overriding the base URL.
+    /// `LCSC_BASE_URL` overrides the LCSC base URL. Variables are read
+    /// through `env_var` so tests don't have to touch the environment.
+    fn apply_overrides(&mut self, user: UserConfig, env_var: impl Fn(&str) -> Option<String>) {
+        if let Some(editor) = env_var("PARTMAN_EDITOR") {
+            self.editor_cmd = editor;
+        } else if self.editor_cmd.trim().is_empty() {
+            let fallback = user.editor_cmd
+                .filter(|e| !e.trim().is_empty())
+                .or_else(|| env_var("VISUAL"))
+                .or_else(|| env_var("EDITOR"));
+            if let Some(editor) = fallback {
+                self.editor_cmd = editor;
+            }
+        }
+
+        let user_digikey = user.digikey.unwrap_or_default();
+        let settings = std::mem::take(&mut self.digikey_settings);
+        self.digikey_settings = settings.or(user_digikey.settings);
+        if let Some(url) = env_var("DIGIKEY_BASE_URL") {
+            self.digikey_settings.base_url = Some(url);
+        }
+        if let Some(url) = env_var("LCSC_BASE_URL") {
+            self.lcsc_settings.base_url = Some(url);
+        }
+
+        let has_section = self.digikey.is_some() || user_digikey.has_credentials;
+        let project = self.digikey.take().unwrap_or_default();
+        let user = user_digikey.credentials;
+        let pick = |var: &str, project: String, user: String| {
+            env_var(var)
+                .or(Some(project).filter(|v| !v.trim().is_empty()))
+                .unwrap_or(user)
+        };
+        let digikey = Digikey {
+            client_id: pick("DIGIKEY_CLIENT_ID", project.client_id, user.client_id),
+            client_secret: pick("DIGIKEY_CLIENT_SECRET", project.client_secret, user.client_secret),
+        };
+        if has_section || !digikey.client_id.is_empty() || !digikey.client_secret.is_empty() {
+            self.digikey = Some(digikey);
+        }
+    }
+
+    /// Set `[storage] backend` in partman.toml, leaving the rest of the file
+    /// and its comments as they are
+    pub fn save_storage_backend(&self, backend: StorageBackend) -> Result<()> {
+        let path = self.root.join(Self::CONFIG_NAME);
+        let content = fs::read_to_string(&path)?;
+        let mut doc: toml_edit::DocumentMut = content.parse().map_err(|e: toml_edit::TomlError| Error::ConfigParse {
+            path: path.clone(),
+            line: e.span().map(|span| content[..span.start].matches('\n').count() + 1),
+            message: e.message().to_owned(),
+        })?;
+
+        // `[storage]`, `storage.backend = ...` and `storage = { ... }` are
+        // all tables, anything else can't hold the setting
+        let storage = doc.entry("storage")
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .ok_or_else(|| Error::Validation(format!(
+                "storage in {} is not a table, set [storage] backend = \"{}\" by hand",
+                path.display(),
+                backend.name()
+            )))?;
+        // Replace only the value so comments above the key stay put
+        match storage.get_mut("backend").and_then(toml_edit::Item::as_value_mut) {
+            Some(value) => {
+                let decor = value.decor().clone();
+                *value = backend.name().into();
+                *value.decor_mut() = decor;
+            },
+            None => {
+                storage.insert("backend", toml_edit::value(backend.name()));
+            },
+        }
+
+        fs::write(&path, doc.to_string())?;
+        Ok(())
+    }
+
+    /// DigiKey credentials, erroring if they are missing or left empty
+    pub fn digikey_credentials(&self) -> Result<&Digikey> {
+        let digikey = self.digikey.as_ref()
+            .ok_or(Error::SecretsMissing { service: "digikey" })?;
+        if digikey.client_id.trim().is_empty() {
+            return Err(Error::SecretsEmpty { service: "digikey", field: "client_id" });
+        }
+        if digikey.client_secret.trim().is_empty() {
+            return Err(Error::SecretsEmpty { service: "digikey", field: "client_secret" });
+        }
+        Ok(digikey)
+    }
+
+    pub const DEFAULT_CONFIG: &str = r#"
+# Partman Config File
+
+# Command to run your editor
+# Remove to use the editor from the user config, $VISUAL or $EDITOR
+editor_cmd = "code.cmd --wait --new-window" # VSCode
+# editor_cmd = "nvim" # Neovim
+
+# Path to the database file
+# Can be absolute or relative to partman.toml
+db_file_path = "build/components.db"
+
+# Path to the directory containing the CSV files for each category
+# Can be absolute or relative to partman.toml
+csv_dir_path = "csv/"
+
+# Path to the directory where partman will store temporary files
+# Can be absolute or relative to partman.toml
+history_dir_path = "history/"
+
+# DigiKey API settings, credentials go in secrets.toml
+[digikey]
+site = "AU"
+language = "en"
+currency = "AUD"
+# customer_id = ""
+# sandbox = true # Use the DigiKey sandbox API
+# base_url = "http://localhost:8080" # Override the API host, e.g. for a mock server
+
+# Secondary suppliers searched by `partman crossref` to fill Supplier2/SPN2
+[crossref]
+suppliers = ["LCSC"]
+
+# Supplier order files, see `partman order`
+[order]
+# Each BOM line is ordered from the first of these the part has a part number for
+preferred_suppliers = ["Digi-Key", "Mouser", "LCSC"]
+
+# Stock on hand per part and location, see `partman stock`
+[inventory]
+# Can be absolute or relative to partman.toml
+file_path = "inventory.csv"
+# Every stock change is appended here, with the project name for kits
+ledger_path = "ledger.csv"
+# Add StockQty and StockLocation columns to the KiCad DB so owned parts are easy to spot
+db_columns = false
+
+# Extra DB objects for tools other than KiCad, KiCad only uses the category tables
+[db]
+# FTS5 table `parts_fts` over description, value, MPN, manufacturer and notes
+search_index = false
+# View `all_parts` combining every category with a `category` column
+all_parts_view = false
+# Indexes on MPN, SPN1 and SPN2
+indexes = false
+
+# Local datasheet archive, see `partman datasheets sync`
+[datasheets]
+# Can be absolute or relative to partman.toml
+dir_path = "datasheets/"
+# Download datasheets when adding or refreshing parts
+auto_download = false
+# KiCad DB Datasheet column: "url" for the vendor link, "local" for the downloaded file
+db_link = "url"
+
+# Where parts are kept, `partman convert --to` moves an existing library between backends
+[storage]
+# "csv" keeps one file per category in csv_dir_path, easy to diff and merge in git
+# "sqlite" keeps every part in one database, faster for large libraries
+backend = "csv"
+# Can be absolute or relative to partman.toml
+sqlite_path = "parts.sqlite"
+
+# Categories, each category has a code, a file and a list of subcategories
+[cat.resistor]
+code = "RES"
+filename = "resistor.csv"
+subcategories = ["chip", "through-hole", "potentiometer"]
+
+[cat.capacitor]
+code = "CAP"
+filename = "capacitor.csv"
+subcategories = ["chip", "aluminium"]
+"#;
+}
+
+#[cfg(test)]
+impl Config {
+    /// The default config loaded from a fresh test directory, so every
+    /// path in it points inside that directory
+    pub(crate) fn for_test(name: &str) -> Self {
+        let dir = crate::util::test_dir(name);
+        fs::write(dir.join(Self::CONFIG_NAME), Self::DEFAULT_CONFIG).unwrap();
+        Self::load_unchecked(&dir).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORAGE_SECTION: &str = "[storage]\n";
+    const STORAGE_BACKEND: &str = "backend = \"csv\"\n";
+
+    /// Set the backend in a partman.toml with the given content, returning
+    /// the result and the file afterwards
+    fn save_backend(name: &str, content: &str) -> (Result<()>, String) {
+        let config = Config::for_test(name);
+        let path = config.root.join(Config::CONFIG_NAME);
+        fs::write(&path, content).unwrap();
+        let result = config.save_storage_backend(StorageBackend::Sqlite);
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&config.root).unwrap();
+        (result, saved)
+    }
+
+    #[test]
+    fn saves_storage_backend() {
+        let (result, saved) = save_backend("backend-default", Config::DEFAULT_CONFIG);
+        result.unwrap();
+        let config: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(config.storage.backend, StorageBackend::Sqlite);
+        assert_eq!(saved.lines().count(), Config::DEFAULT_CONFIG.lines().count());
+
+        let spaced = Config::DEFAULT_CONFIG.replace(STORAGE_SECTION, "[ storage ] # where parts live\n");
+        let (result, saved) = save_backend("backend-spaced", &spaced);
+        result.unwrap();
+        assert_eq!(saved, spaced.replace(STORAGE_BACKEND, "backend = \"sqlite\"\n"));
+
+        let missing = Config::DEFAULT_CONFIG.replace(STORAGE_SECTION, "").replace(STORAGE_BACKEND, "");
+        let (result, saved) = save_backend("backend-missing", &missing);
+        result.unwrap();
+        assert!(saved.ends_with("\n[storage]\nbackend = \"sqlite\"\n"));
+    }
+
+    #[test]
+    fn saves_backend_in_any_table_layout() {
+        let without_section = Config::DEFAULT_CONFIG.replace(STORAGE_SECTION, "").replace(STORAGE_BACKEND, "");
+
+        let inline = format!("storage = {{ backend = \"csv\" }}\n{without_section}");
+        let (result, saved) = save_backend("backend-inline", &inline);
+        result.unwrap();
+        assert!(saved.starts_with("storage = { backend = \"sqlite\" }\n"));
+
+        let dotted = format!("storage.backend = \"csv\" # \"sqlite\" is faster\n{without_section}");
+        let (result, saved) = save_backend("backend-dotted", &dotted);
+        result.unwrap();
+        assert!(saved.starts_with("storage.backend = \"sqlite\" # \"sqlite\" is faster\n"));
+
+        let hash = Config::DEFAULT_CONFIG.replace(STORAGE_BACKEND, "backend = \"csv\"\nnote = \"[storage] # not a header\"\n");
+        let (result, saved) = save_backend("backend-hash", &hash);
+        result.unwrap();
+        assert_eq!(saved, hash.replace("backend = \"csv\"", "backend = \"sqlite\""));
+    }
+
+    #[test]
+    fn refuses_storage_that_is_not_a_table() {
+        let broken = format!(
+            "storage = \"csv\"\n{}",
+            Config::DEFAULT_CONFIG.replace(STORAGE_SECTION, "").replace(STORAGE_BACKEND, "")
+        );
+        let (result, saved) = save_backend("backend-string", &broken);
+        assert!(matches!(result, Err(Error::Validation(_))));
+        assert_eq!(saved, broken);
+    }
+
+    /// The default config with `editor_cmd` and secrets.toml credentials
+    /// set, merged with a user config and environment variables
+    fn overridden(editor: &str, secrets: Option<(&str, &str)>, user: &str, env: &[(&str, &str)]) -> Config {
+        let mut config: Config = toml::from_str(Config::DEFAULT_CONFIG).unwrap();
+        config.editor_cmd = editor.to_owned();
+        config.digikey = secrets.map(|(id, secret)| Digikey {
+            client_id: id.to_owned(),
+            client_secret: secret.to_owned(),
+        });
+        let mut user: UserConfig = toml::from_str(user).unwrap();
+        if let Some(digikey) = &mut user.digikey {
+            digikey.has_credentials = !digikey.credentials.client_id.is_empty()
+                || !digikey.credentials.client_secret.is_empty();
+        }
+        config.apply_overrides(user, |name| {
+            env.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+        });
+        config
+    }
+
+    #[test]
+    fn editor_precedence() {
+        let user = "editor_cmd = \"nvim\"";
+        let env = [("PARTMAN_EDITOR", "hx"), ("VISUAL", "emacs"), ("EDITOR", "vi")];
+        assert_eq!(overridden("code --wait", None, user, &env).editor_cmd, "hx");
+        assert_eq!(overridden("code --wait", None, user, &env[1..]).editor_cmd, "code --wait");
+        assert_eq!(overridden(" ", None, user, &env[1..]).editor_cmd, "nvim");
+        assert_eq!(overridden("", None, "", &env[1..]).editor_cmd, "emacs");
+        assert_eq!(overridden("", None, "editor_cmd = \"\"", &env[2..]).editor_cmd, "vi");
+    }
+
+    #[test]
+    fn credential_precedence() {
+        let user = "[digikey]\nclient_id = \"user-id\"\nclient_secret = \"user-secret\"";
+        let env = [("DIGIKEY_CLIENT_ID", "env-id")];
+
+        let config = overridden("", Some(("project-id", "project-secret")), user, &env);
+        let creds = config.digikey_credentials().unwrap();
+        assert_eq!((creds.client_id.as_str(), creds.client_secret.as_str()), ("env-id", "project-secret"));
+
+        // Each credential falls back on its own
+        let config = overridden("", Some(("project-id", "")), user, &[]);
+        let creds = config.digikey_credentials().unwrap();
+        assert_eq!((creds.client_id.as_str(), creds.client_secret.as_str()), ("project-id", "user-secret"));
+
+        let config = overridden("", None, user, &[]);
+        assert_eq!(config.digikey_credentials().unwrap().client_id, "user-id");
+
+        let config = overridden("", None, "", &[]);
+        assert!(matches!(config.digikey_credentials(), Err(Error::SecretsMissing { .. })));
+        let config = overridden("", Some(("project-id", "")), "", &[]);
+        assert!(matches!(config.digikey_credentials(), Err(Error::SecretsEmpty { field: "client_secret", .. })));
+        let config = overridden("", None, "", &[("DIGIKEY_CLIENT_ID", "env-id"), ("DIGIKEY_CLIENT_SECRET", "env-secret")]);
+        assert_eq!(config.digikey_credentials().unwrap().client_secret, "env-secret");
+    }
+
+    #[test]
+    fn missing_config_is_not_found() {
+        let dir = crate::util::test_dir("no-config");
+        let err = Config::load_unchecked(&dir).unwrap_err();
+        assert!(matches!(err, Error::ConfigNotFound { searched_parents: false, .. }));
+        assert_eq!(err.code(), "config_not_found");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_order_suppliers() {
+        let mut config = Config::for_test("order-suppliers");
+        config.order.preferred_suppliers = vec!["lcsc".into(), "Farnell".into()];
+        let issues = config.validate();
+        let keys: Vec<(&str, Severity)> = issues.iter().map(|i| (i.key.as_str(), i.severity)).collect();
+        assert!(keys.contains(&("order.preferred_suppliers", Severity::Error)));
+        assert_eq!(crate::order::Supplier::preferred(&config), [crate::order::Supplier::Lcsc]);
+        fs::remove_dir_all(&config.root).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_crossref_suppliers() {
+        let mut config = Config::for_test("crossref-suppliers");
+        config.crossref.suppliers = vec!["LCSC".into(), "Mouser".into()];
+        let issues = config.validate();
+        assert!(issues.iter().any(|i| i.key == "crossref.suppliers" && i.message.contains("'Mouser'")));
+        fs::remove_dir_all(&config.root).unwrap();
+    }
+
+    /// Keys of the errors found in the default config after `change`
+    fn config_errors(change: impl FnOnce(&mut Config)) -> Vec<String> {
+        let mut config: Config = toml::from_str(Config::DEFAULT_CONFIG).unwrap();
+        change(&mut config);
+        let mut keys: Vec<String> = config.validate().into_iter()
+            .filter(|i| i.severity == Severity::Error)
+            .map(|i| i.key)
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Add a category copied from resistor under another name
+    fn add_cat(config: &mut Config, name: &str, code: &str, filename: &str) {
+        let mut cat = config.cat["resistor"].clone();
+        cat.code = code.into();
+        cat.filename = filename.into();
+        config.cat.insert(name.into(), cat);
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(config_errors(|_| {}).is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_category_names() {
+        for name in ["select", "2n_fets", "diode-tvs", "all_parts", "parts_fts", "Diode"] {
+            assert_eq!(
+                config_errors(|c| add_cat(c, name, "DIO", "diode.csv")),
+                [format!("cat.{name}")],
+                "{name}"
+            );
+        }
+        assert!(config_errors(|c| add_cat(c, "tvs_diode", "TVS", "tvs.csv")).is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_codes_and_filenames() {
+        assert_eq!(config_errors(|c| add_cat(c, "diode", " ", "diode.csv")), ["cat.diode.code"]);
+        assert_eq!(config_errors(|c| add_cat(c, "diode", "DI-O", "diode.csv")), ["cat.diode.code"]);
+        // The later of the two categories in name order is the one reported
+        assert_eq!(config_errors(|c| add_cat(c, "diode", "RES", "diode.csv")), ["cat.resistor.code"]);
+        assert_eq!(config_errors(|c| add_cat(c, "diode", "DIO", "capacitor.csv")), ["cat.diode.filename"]);
+    }
+
+    #[test]
+    fn rejects_empty_categories() {
+        assert_eq!(
+            config_errors(|c| c.cat.get_mut("capacitor").unwrap().subcategories.clear()),
+            ["cat.capacitor.subcategories"]
+        );
+        assert_eq!(config_errors(|c| c.cat.clear()), ["cat"]);
+    }
+
+    #[test]
+    fn reserves_stock_levels_file() {
+        let mut config = Config::for_test("reserved");
+        config.cat.get_mut("resistor").unwrap().filename = "stock_levels.csv".into();
+        let issues = config.validate();
+        assert!(issues.iter().any(|i| i.key == "cat.resistor.filename" && i.severity == Severity::Error));
+        fs::remove_dir_all(&config.root).unwrap();
+    }
+}
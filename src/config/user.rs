@@ -0,0 +1,100 @@
+//! User level config shared by every library.
+//!
+//! Lives in `$XDG_CONFIG_HOME/partman/config.toml` (or
+//! `~/.config/partman/config.toml`) and holds personal settings such as the
+//! editor and API credentials. Values in a project's partman.toml and
+//! secrets.toml take precedence over it.
+
+use std::{env, fs, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::{Error, Result};
+
+use super::{Digikey, DigikeySettings};
+
+/// How the CLI prints command results
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn is_json(self) -> bool {
+        self == OutputFormat::Json
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UserConfig {
+    /// Editor used when the project config does not set one
+    pub editor_cmd: Option<String>,
+    /// Default output format when `--output` is not given
+    pub output: Option<OutputFormat>,
+    pub digikey: Option<UserDigikey>,
+}
+
+/// The user config `[digikey]` section, holding both credentials and settings
+#[derive(Debug, Default, Deserialize)]
+pub struct UserDigikey {
+    #[serde(flatten)]
+    pub credentials: Digikey,
+    #[serde(flatten)]
+    pub settings: DigikeySettings,
+    #[serde(skip)]
+    pub has_credentials: bool,
+}
+
+impl UserConfig {
+    pub const DEFAULT_USER_CONFIG: &str = r#"
+# Partman User Config File, shared by every library
+
+# Command to run your editor
+# editor_cmd = "nvim"
+
+# Default output format, "text" or "json"
+# output = "text"
+
+# Digikey API credentials, get them from https://developer.digikey.com/
+# Locale settings here are used when a library's partman.toml does not set them
+# [digikey]
+# client_id = ""
+# client_secret = ""
+# site = "AU"
+# language = "en"
+# currency = "AUD"
+"#;
+
+    /// Path to the user config file, `None` if no config directory is known
+    pub fn path() -> Option<PathBuf> {
+        let dir = match env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None if cfg!(windows) => PathBuf::from(env::var_os("APPDATA")?),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(dir.join("partman").join("config.toml"))
+    }
+
+    /// Load the user config, or an empty one if the file does not exist
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path().filter(|p| p.exists()) else {
+            return Ok(Self::default());
+        };
+        let content = fs::read_to_string(&path)?;
+        let mut user: Self = toml::from_str(&content)
+            .map_err(|e| Error::config_parse(&path, &content, e))?;
+        if let Some(digikey) = &mut user.digikey {
+            digikey.has_credentials = !digikey.credentials.client_id.is_empty()
+                || !digikey.credentials.client_secret.is_empty();
+        }
+        Ok(user)
+    }
+}
+
+/// Read an environment variable, ignoring it if unset or empty
+pub(super) fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
@@ -0,0 +1,173 @@
+//! Config validation.
+//!
+//! Catches config mistakes that would otherwise fail late or silently
+//! corrupt the generated database, such as category names that are not
+//! valid SQL table names.
+
+use std::{collections::HashMap, env, path::Path};
+
+use serde::Serialize;
+
+use super::Config;
+use crate::{crossref::Backend, data, order::Supplier};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Config cannot be used, loading fails
+    Error,
+    /// Config works but something is likely wrong
+    Warning,
+}
+
+/// A single problem found in the config
+#[derive(Debug, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Config key the issue relates to, e.g. `cat.resistor.code`
+    pub key: String,
+    pub message: String,
+}
+
+impl Issue {
+    fn error(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, key: key.into(), message: message.into() }
+    }
+
+    fn warning(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, key: key.into(), message: message.into() }
+    }
+}
+
+/// SQLite keywords that cannot be used as unquoted table names
+const SQL_KEYWORDS: &[&str] = &[
+    "abort", "add", "all", "alter", "and", "as", "asc", "between", "by", "case", "check",
+    "collate", "column", "commit", "constraint", "create", "cross", "default", "delete",
+    "desc", "distinct", "drop", "else", "end", "escape", "except", "exists", "foreign",
+    "from", "full", "group", "having", "if", "in", "index", "inner", "insert", "intersect",
+    "into", "is", "join", "key", "left", "like", "limit", "natural", "not", "null", "of",
+    "offset", "on", "or", "order", "outer", "primary", "references", "right", "rollback",
+    "select", "set", "table", "then", "to", "transaction", "union", "unique", "update",
+    "using", "values", "view", "when", "where", "with",
+];
+
+fn is_sql_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let valid_start = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    valid_start
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !SQL_KEYWORDS.contains(&name.to_ascii_lowercase().as_str())
+}
+
+/// Whether a command can be found, either as a path or on `PATH`
+fn command_exists(cmd: &str) -> bool {
+    let path = Path::new(cmd);
+    if path.components().count() > 1 {
+        return path.exists();
+    }
+
+    let Some(paths) = env::var_os("PATH") else {
+        return false;
+    };
+    env::split_paths(&paths).any(|dir| {
+        let candidate = dir.join(cmd);
+        candidate.exists() || candidate.with_extension("exe").exists()
+    })
+}
+
+impl Config {
+    /// Check the config for problems, returning every issue found
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = Vec::new();
+
+        match self.editor_cmd.split_whitespace().next() {
+            None => issues.push(Issue::warning("editor_cmd", "No editor command set")),
+            Some(cmd) if !command_exists(cmd) => issues.push(Issue::warning(
+                "editor_cmd",
+                format!("Editor '{cmd}' was not found on PATH"),
+            )),
+            Some(_) => {},
+        }
+
+        if self.cat.is_empty() {
+            issues.push(Issue::error("cat", "No categories defined"));
+        }
+
+        // Sort so issues are reported in a stable order
+        let mut names: Vec<&String> = self.cat.keys().collect();
+        names.sort();
+
+        let mut codes: HashMap<&str, &str> = HashMap::new();
+        let mut filenames: HashMap<&Path, &str> = HashMap::new();
+
+        for name in names {
+            let cat = &self.cat[name];
+            let key = format!("cat.{name}");
+
+            if !is_sql_identifier(name) {
+                issues.push(Issue::error(&key, format!(
+                    "Category '{name}' is not a valid SQL table name, use letters, digits and underscores and avoid SQL keywords"
+                )));
+            }
+            if data::RESERVED_TABLES.contains(&name.as_str()) {
+                issues.push(Issue::error(&key, format!(
+                    "Category '{name}' is reserved for partman's own DB objects"
+                )));
+            }
+            if name.chars().any(|c| c.is_uppercase()) {
+                issues.push(Issue::error(&key, format!(
+                    "Category '{name}' must be lowercase, categories entered in forms are lowercased"
+                )));
+            }
+
+            if cat.code.trim().is_empty() {
+                issues.push(Issue::error(format!("{key}.code"), "Category code is empty"));
+            } else if cat.code.contains('-') {
+                issues.push(Issue::error(format!("{key}.code"), format!(
+                    "Category code '{}' must not contain '-', it separates the code from the number in IDs",
+                    cat.code
+                )));
+            }
+            if let Some(other) = codes.insert(&cat.code, name) {
+                issues.push(Issue::error(format!("{key}.code"), format!(
+                    "Code '{}' is also used by category '{other}'",
+                    cat.code
+                )));
+            }
+
+            if Path::new(&cat.filename) == Path::new(data::LEVELS_FILE) {
+                issues.push(Issue::error(format!("{key}.filename"), format!(
+                    "File '{}' is reserved for stock levels",
+                    cat.filename
+                )));
+            }
+            if let Some(other) = filenames.insert(Path::new(&cat.filename), name) {
+                issues.push(Issue::error(format!("{key}.filename"), format!(
+                    "File '{}' is also used by category '{other}'",
+                    cat.filename
+                )));
+            }
+
+            if cat.subcategories.is_empty() {
+                issues.push(Issue::error(format!("{key}.subcategories"), "No subcategories defined"));
+            }
+        }
+
+        for name in &self.order.preferred_suppliers {
+            if Supplier::from_name(name).is_none() {
+                issues.push(Issue::error("order.preferred_suppliers", format!(
+                    "Unknown supplier '{name}', expected Digi-Key, Mouser or LCSC"
+                )));
+            }
+        }
+        for name in &self.crossref.suppliers {
+            if !Backend::is_supported(name) {
+                issues.push(Issue::error("crossref.suppliers", format!(
+                    "Unknown supplier '{name}', expected LCSC or DigiKey"
+                )));
+            }
+        }
+
+        issues
+    }
+}
@@ -0,0 +1,190 @@
+//! Board cost roll-up from DigiKey price breaks.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{
+    api::digikey::{self, DigikeyProduct, PriceBreak, ProductVariation},
+    bom::BomLine,
+    Result,
+};
+
+/// Price breaks and minimum order quantity for a part's DigiKey variation
+#[derive(Debug, Clone)]
+pub struct Pricing {
+    pub spn: String,
+    pub moq: u32,
+    pub breaks: Vec<PriceBreak>,
+}
+
+/// The variation matching the SPN, falling back to the preferred variation
+/// if DigiKey returned a different part number
+pub fn find_variation<'a>(product: &'a DigikeyProduct, spn: &str) -> Result<&'a ProductVariation> {
+    product.variation(spn)
+        .or_else(|| product.preferred_spn().and_then(|s| product.variation(s)))
+        .ok_or_else(|| format!("No DigiKey product variation for '{spn}'").into())
+}
+
+/// Look up the price breaks of the variation matching the SPN
+pub fn fetch_pricing(client: &digikey::Client, spn: &str) -> Result<Pricing> {
+    let product = client.get_product(spn)?;
+    let variation = find_variation(&product, spn)?;
+
+    let mut breaks = variation.standard_pricing.clone();
+    breaks.sort_by_key(|b| b.break_qty);
+    if breaks.is_empty() {
+        return Err(format!("DigiKey has no standard pricing for '{spn}'").into());
+    }
+    Ok(Pricing {
+        spn: variation.digikey_product_number.clone(),
+        moq: variation.minimum_order_qty,
+        breaks,
+    })
+}
+
+/// The cheapest way to buy at least `needed`, as order quantity, unit price
+/// and extended price. Buying up to a higher break is chosen when it costs
+/// less in total, and the order is never below the minimum order quantity.
+pub fn best_price(pricing: &Pricing, needed: u32) -> (u32, f64, f64) {
+    let first = pricing.breaks.first().map(|b| b.break_qty).unwrap_or(1);
+    let base = needed.max(pricing.moq).max(first);
+
+    let unit_at = |qty: u32| pricing.breaks.iter()
+        .rfind(|b| b.break_qty <= qty)
+        .map(|b| b.unit_price)
+        .unwrap_or_default();
+
+    std::iter::once(base)
+        .chain(pricing.breaks.iter().map(|b| b.break_qty).filter(|&q| q > base))
+        .map(|qty| (qty, unit_at(qty), unit_at(qty) * f64::from(qty)))
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+        .unwrap_or((base, 0.0, 0.0))
+}
+
+/// A priced BOM line
+#[derive(Debug, Serialize)]
+pub struct CostLine {
+    pub id: String,
+    pub spn: String,
+    /// Quantity needed for every board
+    pub needed: u32,
+    /// Quantity to order, raised to the MOQ or a cheaper price break
+    pub order_qty: u32,
+    pub unit_price: f64,
+    pub extended: f64,
+    pub references: Vec<String>,
+}
+
+/// A BOM line that could not be priced
+#[derive(Debug, Serialize)]
+pub struct Unpriced {
+    pub id: Option<String>,
+    pub lib_id: String,
+    pub value: String,
+    pub reason: String,
+}
+
+/// Cost of building a number of boards
+#[derive(Debug, Serialize)]
+pub struct Rollup {
+    pub boards: u32,
+    pub currency: String,
+    pub lines: Vec<CostLine>,
+    pub unpriced: Vec<Unpriced>,
+    pub total: f64,
+    pub per_board: f64,
+}
+
+/// Price every line for `boards` boards, using pricing keyed by part ID
+pub fn rollup(lines: &[BomLine], pricing: &HashMap<String, Result<Pricing>>, boards: u32, currency: &str) -> Rollup {
+    let mut rollup = Rollup {
+        boards,
+        currency: currency.to_owned(),
+        lines: Vec::new(),
+        unpriced: Vec::new(),
+        total: 0.0,
+        per_board: 0.0,
+    };
+
+    for line in lines {
+        let unpriced = |reason: String| Unpriced {
+            id: line.id.clone(),
+            lib_id: line.lib_id.clone(),
+            value: line.value.clone(),
+            reason,
+        };
+        let Some(id) = &line.id else {
+            rollup.unpriced.push(unpriced("not in library".into()));
+            continue;
+        };
+        let pricing = match pricing.get(id) {
+            None => {
+                rollup.unpriced.push(unpriced("no DigiKey part number".into()));
+                continue;
+            },
+            Some(Err(e)) => {
+                rollup.unpriced.push(unpriced(e.to_string()));
+                continue;
+            },
+            Some(Ok(pricing)) => pricing,
+        };
+
+        let needed = u32::try_from(line.qty).unwrap_or(u32::MAX).saturating_mul(boards);
+        let (order_qty, unit_price, extended) = best_price(pricing, needed);
+        rollup.total += extended;
+        rollup.lines.push(CostLine {
+            id: id.clone(),
+            spn: pricing.spn.clone(),
+            needed,
+            order_qty,
+            unit_price,
+            extended,
+            references: line.references.clone(),
+        });
+    }
+
+    if boards > 0 {
+        rollup.per_board = rollup.total / f64::from(boards);
+    }
+    rollup
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pricing(moq: u32) -> Pricing {
+        let price_break = |break_qty: u32, unit_price: f64| PriceBreak {
+            break_qty,
+            unit_price,
+            total_price: unit_price * f64::from(break_qty),
+        };
+        Pricing {
+            spn: "1276-1001-1-ND".into(),
+            moq,
+            breaks: vec![price_break(1, 0.10), price_break(10, 0.05), price_break(100, 0.02)],
+        }
+    }
+
+    #[test]
+    fn buys_the_needed_quantity() {
+        assert_eq!(best_price(&pricing(1), 4), (4, 0.10, 0.4));
+        assert_eq!(best_price(&pricing(1), 30), (30, 0.05, 1.5));
+    }
+
+    #[test]
+    fn buys_up_to_a_cheaper_break() {
+        assert_eq!(best_price(&pricing(1), 8), (10, 0.05, 0.5));
+        assert_eq!(best_price(&pricing(1), 60), (100, 0.02, 2.0));
+    }
+
+    #[test]
+    fn never_buys_below_the_moq() {
+        assert_eq!(best_price(&pricing(250), 5), (250, 0.02, 5.0));
+
+        // Above the MOQ the break for the raised quantity applies
+        let (qty, unit, _) = best_price(&pricing(25), 3);
+        assert_eq!((qty, unit), (25, 0.05));
+    }
+}
@@ -0,0 +1,280 @@
+//! Cross-reference parts to second source supplier part numbers by MPN.
+
+use serde::Serialize;
+
+use crate::{
+    api::{digikey, lcsc},
+    config::Config,
+    part::Part,
+    Result,
+};
+
+/// A supplier part matching a part's MPN
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub supplier: String,
+    pub spn: String,
+    pub mpn: String,
+    pub manufacturer: Option<String>,
+}
+
+/// A supplier that can be searched by MPN
+pub enum Backend {
+    Lcsc(lcsc::Client),
+    Digikey(digikey::Client),
+}
+
+impl Backend {
+    /// Whether a `[crossref] suppliers` name is one a backend exists for
+    pub fn is_supported(name: &str) -> bool {
+        matches!(normalise(name).as_str(), "lcsc" | "digikey")
+    }
+
+    /// Create the backend for a supplier name from `[crossref] suppliers`
+    pub fn from_name(name: &str, config: &Config) -> Result<Self> {
+        match normalise(name).as_str() {
+            "lcsc" => Ok(Backend::Lcsc(lcsc::Client::from_config(config))),
+            "digikey" => Ok(Backend::Digikey(digikey::Client::from_config(config)?)),
+            _ => Err(format!("Unknown crossref supplier '{name}', expected LCSC or DigiKey").into()),
+        }
+    }
+
+    /// Supplier name as written into the Supplier2 column
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Lcsc(_) => "LCSC",
+            Backend::Digikey(_) => "Digi-Key",
+        }
+    }
+
+    /// Products whose MPN exactly matches
+    fn search(&self, mpn: &str) -> Result<Vec<Candidate>> {
+        let candidates = match self {
+            Backend::Lcsc(client) => client.find_by_mpn(mpn)?
+                .into_iter()
+                .map(|p| Candidate {
+                    supplier: self.name().to_owned(),
+                    spn: p.code,
+                    mpn: p.mpn,
+                    manufacturer: p.manufacturer,
+                })
+                .collect(),
+            Backend::Digikey(client) => client.search_keyword(mpn, 10)?
+                .into_iter()
+                .filter(|p| p.manufacturer_product_number.eq_ignore_ascii_case(mpn.trim()))
+                .filter_map(|p| Some(Candidate {
+                    supplier: self.name().to_owned(),
+                    spn: p.search_spn?,
+                    mpn: p.manufacturer_product_number,
+                    manufacturer: Some(p.manufacturer.name),
+                }))
+                .collect(),
+        };
+        Ok(candidates)
+    }
+}
+
+/// Lowercase and strip punctuation, "Digi-Key" becomes "digikey"
+fn normalise(s: &str) -> String {
+    s.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase()
+}
+
+/// Whether two manufacturer names refer to the same company, allowing
+/// for suffixes like "Samsung" vs "Samsung Electro-Mechanics"
+fn same_manufacturer(a: &str, b: &str) -> bool {
+    let (a, b) = (normalise(a), normalise(b));
+    !a.is_empty() && !b.is_empty() && (a.starts_with(&b) || b.starts_with(&a))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum Outcome {
+    /// A single match was found and written to the second supplier slot
+    Filled { candidate: Candidate },
+    /// Several suppliers parts match, needs manual resolution
+    Ambiguous { candidates: Vec<Candidate> },
+    NotFound,
+    /// Part has no MPN, or already has a second supplier part number
+    Skipped { reason: String },
+    Error { message: String },
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrossrefResult {
+    pub id: String,
+    pub mpn: Option<String>,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+/// Find a second source for a part, filling `supplier2`/`spn2` on a unique match
+pub fn crossref_part(backends: &[Backend], part: &mut Part) -> CrossrefResult {
+    let mut result = CrossrefResult {
+        id: part.id.clone().unwrap_or_default(),
+        mpn: part.mpn.clone(),
+        outcome: Outcome::NotFound,
+    };
+
+    let Some(mpn) = part.mpn.clone() else {
+        result.outcome = Outcome::Skipped { reason: "no MPN".into() };
+        return result;
+    };
+    if part.spn2.is_some() {
+        result.outcome = Outcome::Skipped { reason: "already has SPN2".into() };
+        return result;
+    }
+
+    // A named but empty second slot limits the search to that supplier
+    let primary = part.supplier1.as_deref().map(normalise);
+    let wanted = part.supplier2.as_deref().map(normalise);
+    let backends = backends.iter().filter(|b| {
+        let name = normalise(b.name());
+        Some(&name) != primary.as_ref() && wanted.as_ref().is_none_or(|w| w == &name)
+    });
+
+    for backend in backends {
+        let mut candidates = match backend.search(&mpn) {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                result.outcome = Outcome::Error { message: format!("{}: {e}", backend.name()) };
+                continue;
+            },
+        };
+        if let Some(manufacturer) = &part.manufacturer {
+            candidates.retain(|c| c.manufacturer.as_deref().is_none_or(|m| same_manufacturer(m, manufacturer)));
+        }
+
+        match candidates.len() {
+            0 => {},
+            1 => {
+                let candidate = candidates.remove(0);
+                part.supplier2 = Some(candidate.supplier.clone());
+                part.spn2 = Some(candidate.spn.clone());
+                result.outcome = Outcome::Filled { candidate };
+                return result;
+            },
+            _ => {
+                result.outcome = Outcome::Ambiguous { candidates };
+                return result;
+            },
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+    use crate::config::LcscSettings;
+
+    /// An LCSC backend answering every search with these products
+    fn lcsc(products: &[(&str, &str, &str)]) -> Backend {
+        let products: Vec<serde_json::Value> = products.iter()
+            .map(|(code, mpn, manufacturer)| serde_json::json!({
+                "productCode": code,
+                "productModel": mpn,
+                "brandNameEn": manufacturer,
+            }))
+            .collect();
+        let body = serde_json::json!({
+            "code": 200,
+            "result": { "productSearchResultVO": { "productList": products } },
+        }).to_string();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let _ = stream.read(&mut [0; 4096]);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+        Backend::Lcsc(lcsc::Client::new(&LcscSettings { base_url: Some(base_url) }))
+    }
+
+    fn part(mpn: Option<&str>) -> Part {
+        let mut part = Part::for_test("CAP-0001", "capacitor");
+        part.mpn = mpn.map(String::from);
+        part.manufacturer = Some("Samsung".into());
+        part.supplier1 = Some("Digi-Key".into());
+        part.spn1 = Some("1276-1001-1-ND".into());
+        part
+    }
+
+    #[test]
+    fn manufacturers_match_by_prefix() {
+        assert!(same_manufacturer("Samsung", "SAMSUNG Electro-Mechanics"));
+        assert!(same_manufacturer("Yageo Corp.", "YAGEO"));
+        assert!(!same_manufacturer("TI", "Texas Instruments"));
+        assert!(!same_manufacturer("", "YAGEO"));
+    }
+
+    #[test]
+    fn supports_lcsc_and_digikey() {
+        assert!(Backend::is_supported("LCSC") && Backend::is_supported("Digi-Key") && Backend::is_supported("digikey"));
+        assert!(!Backend::is_supported("Mouser"));
+    }
+
+    #[test]
+    fn skips_parts_without_mpn_or_with_spn2() {
+        let backends = [lcsc(&[])];
+        let result = crossref_part(&backends, &mut part(None));
+        assert!(matches!(result.outcome, Outcome::Skipped { ref reason } if reason == "no MPN"));
+
+        let mut filled = part(Some("CL05B104KO5NNNC"));
+        filled.spn2 = Some("C1525".into());
+        let result = crossref_part(&backends, &mut filled);
+        assert!(matches!(result.outcome, Outcome::Skipped { ref reason } if reason == "already has SPN2"));
+    }
+
+    #[test]
+    fn fills_a_unique_match_from_the_same_manufacturer() {
+        let backends = [lcsc(&[
+            ("C1525", "CL05B104KO5NNNC", "SAMSUNG Electro-Mechanics"),
+            ("C9999", "CL05B104KO5NNNC", "Other Corp"),
+        ])];
+        let mut part = part(Some("CL05B104KO5NNNC"));
+        let result = crossref_part(&backends, &mut part);
+        assert!(matches!(result.outcome, Outcome::Filled { ref candidate } if candidate.spn == "C1525"));
+        assert_eq!((part.supplier2.as_deref(), part.spn2.as_deref()), (Some("LCSC"), Some("C1525")));
+    }
+
+    #[test]
+    fn leaves_ambiguous_and_missing_parts_alone() {
+        let backends = [lcsc(&[
+            ("C1525", "CL05B104KO5NNNC", "Samsung"),
+            ("C1526", "CL05B104KO5NNNC", "Samsung"),
+        ])];
+        let mut ambiguous = part(Some("CL05B104KO5NNNC"));
+        let result = crossref_part(&backends, &mut ambiguous);
+        assert!(matches!(result.outcome, Outcome::Ambiguous { ref candidates } if candidates.len() == 2));
+        assert_eq!(ambiguous.spn2, None);
+
+        let result = crossref_part(&[lcsc(&[])], &mut part(Some("CL05B104KO5NNNC")));
+        assert!(matches!(result.outcome, Outcome::NotFound));
+
+        // The primary supplier is never searched for a second source
+        let mut lcsc_primary = part(Some("CL05B104KO5NNNC"));
+        lcsc_primary.supplier1 = Some("LCSC".into());
+        let result = crossref_part(&backends, &mut lcsc_primary);
+        assert!(matches!(result.outcome, Outcome::NotFound));
+    }
+
+    #[test]
+    fn reports_supplier_errors() {
+        let unreachable = Backend::Lcsc(lcsc::Client::new(&LcscSettings { base_url: Some("http://127.0.0.1:1".into()) }));
+        let result = crossref_part(&[unreachable], &mut part(Some("CL05B104KO5NNNC")));
+        assert!(matches!(result.outcome, Outcome::Error { ref message } if message.starts_with("LCSC: ")));
+    }
+}
@@ -1,10 +1,11 @@
-use std::collections::HashMap;
-use std::path::Path;
-use csv::{ReaderBuilder, WriterBuilder};
-use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use csv::{QuoteStyle, ReaderBuilder, Terminator, WriterBuilder};
+use std::fs;
 
-use crate::config::{Cat, Config};
-use crate::{part::Part, Result, util::Normalise};
+use crate::config::Config;
+use crate::{part::{Part, SupplierData}, Error, Result, util::Normalise};
+
+use super::PartStore;
 
 /// The CSV representation of a component. This maps *exactly* to CSV columns.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -56,6 +57,59 @@ pub struct CsvRow {
 
     #[serde(rename = "Notes")]
     pub notes: String,
+
+    // Supplier data columns are optional so older files without them load
+    #[serde(rename = "UnitPrice", default)]
+    pub unit_price: Option<f64>,
+
+    #[serde(rename = "QtyAvailable", default)]
+    pub qty_available: Option<u32>,
+
+    #[serde(rename = "ProductStatus", default)]
+    pub product_status: Option<String>,
+
+    #[serde(rename = "Discontinued", default, deserialize_with = "any_case_bool")]
+    pub discontinued: Option<bool>,
+
+    #[serde(rename = "EndOfLife", default, deserialize_with = "any_case_bool")]
+    pub end_of_life: Option<bool>,
+
+    #[serde(rename = "LastChecked", default)]
+    pub last_checked: Option<String>,
+
+    #[serde(rename = "JlcType", default)]
+    pub jlc_type: Option<String>,
+
+    #[serde(rename = "DatasheetFile", default)]
+    pub datasheet_file: Option<String>,
+
+    #[serde(rename = "DatasheetSha256", default)]
+    pub datasheet_sha256: Option<String>,
+
+    #[serde(rename = "DatasheetSource", default)]
+    pub datasheet_source: Option<String>,
+}
+
+/// Spreadsheets save booleans as `TRUE` and `FALSE`, so accept any case
+fn any_case_bool<'de, D: serde::Deserializer<'de>>(deserializer: D) -> core::result::Result<Option<bool>, D::Error> {
+    let value: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(s) if s.eq_ignore_ascii_case("true") => Ok(Some(true)),
+        Some(s) if s.eq_ignore_ascii_case("false") => Ok(Some(false)),
+        Some(s) => Err(serde::de::Error::custom(format!("invalid boolean '{s}', expected true or false"))),
+    }
+}
+
+impl CsvRow {
+    /// Column headers, in CSV order
+    pub const HEADERS: [&str; 26] = [
+        "ID", "Subcategory", "Description", "Value", "Manufacturer", "MPN", "Package",
+        "Supplier1", "SPN1", "Supplier2", "SPN2",
+        "Symbol", "Footprint", "Datasheet", "Status", "Notes",
+        "UnitPrice", "QtyAvailable", "ProductStatus", "Discontinued", "EndOfLife", "LastChecked",
+        "JlcType", "DatasheetFile", "DatasheetSha256", "DatasheetSource",
+    ];
 }
 
 impl From<CsvRow> for Part {
@@ -78,6 +132,18 @@ impl From<CsvRow> for Part {
             datasheet: r.datasheet.none_if_empty(),
             status: r.status,
             notes: r.notes.none_if_empty(),
+            supplier_data: SupplierData {
+                unit_price: r.unit_price,
+                qty_available: r.qty_available,
+                product_status: r.product_status.and_then(|s| s.none_if_empty()),
+                discontinued: r.discontinued,
+                end_of_life: r.end_of_life,
+                last_checked: r.last_checked.and_then(|s| s.none_if_empty()),
+            },
+            jlc_type: r.jlc_type.and_then(|s| s.none_if_empty()),
+            datasheet_file: r.datasheet_file.and_then(|s| s.none_if_empty()),
+            datasheet_sha256: r.datasheet_sha256.and_then(|s| s.none_if_empty()),
+            datasheet_source: r.datasheet_source.and_then(|s| s.none_if_empty()),
         }
     }
 }
@@ -101,64 +167,249 @@ impl From<Part> for CsvRow {
             datasheet: p.datasheet.unwrap_or_default(),
             status: p.status,
             notes: p.notes.unwrap_or_default(),
+            unit_price: p.supplier_data.unit_price,
+            qty_available: p.supplier_data.qty_available,
+            product_status: p.supplier_data.product_status,
+            discontinued: p.supplier_data.discontinued,
+            end_of_life: p.supplier_data.end_of_life,
+            last_checked: p.supplier_data.last_checked,
+            jlc_type: p.jlc_type,
+            datasheet_file: p.datasheet_file,
+            datasheet_sha256: p.datasheet_sha256,
+            datasheet_source: p.datasheet_source,
         }
     }
 }
 
 
 
-pub fn load_all_parts(categories: &HashMap<String, Cat>, csv_dir: &Path) -> Result<Vec<Part>> {
-    let mut parts = Vec::new();
+/// Parts kept in one CSV file per category, in `csv_dir_path`
+pub struct CsvStore<'a> {
+    config: &'a Config,
+}
+
+impl<'a> CsvStore<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Self { config }
+    }
+
+    fn category_path(&self, category: &str) -> Result<PathBuf> {
+        let category_config = self.config.cat.get(category)
+            .ok_or_else(|| Error::Validation(format!("Invalid category '{category}'")))?;
+        Ok(self.config.csv_dir_path.join(&category_config.filename))
+    }
 
-    for (category, cat_cfg) in categories {
-        let path = csv_dir.join(&cat_cfg.filename);
-        if !path.exists() {
-            continue;
+    /// Rewrite every category file not already in canonical form, returning
+    /// the files that were changed. With `check` nothing is written and the
+    /// files that would change are returned.
+    pub fn format(&self, check: bool) -> Result<Vec<PathBuf>> {
+        let mut categories: Vec<&String> = self.config.cat.keys().collect();
+        categories.sort();
+
+        let mut changed = Vec::new();
+        for category in categories {
+            let path = self.category_path(category)?;
+            if !path.exists() {
+                continue;
+            }
+            check_columns(&path)?;
+            let canonical = canonical_csv(&load_category(category, &path)?)?;
+            if fs::read(&path)? != canonical {
+                if !check {
+                    fs::write(&path, canonical)?;
+                }
+                changed.push(path);
+            }
         }
+        Ok(changed)
+    }
+}
 
-        let mut rdr = ReaderBuilder::new().from_path(path)?;
-        for row in rdr.deserialize::<CsvRow>() {
-            let mut part: Part = row?.into();
-            part.category = category.to_string();
-            parts.push(part);
+impl PartStore for CsvStore<'_> {
+    fn load(&self) -> Result<Vec<Part>> {
+        let mut parts = Vec::new();
+        for (category, cat_cfg) in &self.config.cat {
+            parts.extend(load_category(category, &self.config.csv_dir_path.join(&cat_cfg.filename))?);
         }
+        Ok(parts)
     }
 
-    Ok(parts)
+    fn insert(&self, part: &Part) -> Result<()> {
+        let csv_path = self.category_path(&part.category)?;
+        let mut parts = load_category(&part.category, &csv_path)?;
+        parts.push(part.clone());
+        write_category(&parts, &csv_path)
+    }
+
+    fn save_categories(&self, parts: &[Part], categories: &[&str]) -> Result<()> {
+        for &category in categories {
+            let cat_parts: Vec<Part> = parts.iter()
+                .filter(|p| p.category == category)
+                .cloned()
+                .collect();
+            write_category(&cat_parts, &self.category_path(category)?)?;
+        }
+        Ok(())
+    }
 }
 
+fn load_category(category: &str, path: &Path) -> Result<Vec<Part>> {
+    let mut parts = Vec::new();
+    if !path.exists() {
+        return Ok(parts);
+    }
 
-pub fn insert_part(part: &mut Part, config: &Config, library_path: &Path) -> Result<()> {
-    let category_config = config.cat.get(&part.category)
-        .ok_or_else(|| format!("Invalid category '{}'", part.category))?;
+    // Spreadsheets often save with a byte order mark, which would become
+    // part of the first header
+    let content = fs::read(path)?;
+    let content = content.strip_prefix(UTF8_BOM).unwrap_or(&content);
 
-    let csv_path = library_path.join(&category_config.filename);
+    let mut rdr = ReaderBuilder::new().from_reader(content);
+    for row in rdr.deserialize::<CsvRow>() {
+        let mut part: Part = row.map_err(|e| Error::csv(path, &CsvRow::HEADERS, e))?.into();
+        part.category = category.to_string();
+        parts.push(part);
+    }
 
-    let row: CsvRow = part.clone().into();
+    Ok(parts)
+}
 
-    let file_exists = csv_path.exists();
-    let file = OpenOptions::new().append(true).create(true).open(csv_path)?;
-    let mut wtr = WriterBuilder::new().has_headers(!file_exists).from_writer(file);
-    wtr.serialize(row)?;
-    wtr.flush()?;
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
 
-    Ok(())
+/// Sort key so IDs compare by category code then number, `RES-10000` after
+/// `RES-9999`
+fn id_key(part: &Part) -> (&str, u64, &str) {
+    let id = part.id.as_deref().unwrap_or_default();
+    let (code, number) = id.rsplit_once('-').unwrap_or((id, ""));
+    (code, number.parse().unwrap_or(u64::MAX), id)
 }
 
-pub fn get_next_id(parts: &Vec<Part>, category_code: &str) -> Result<String> {
-    let max = parts
+/// The canonical file for a category: UTF-8 without a byte order mark, the
+/// full header row, rows sorted by ID, fields quoted only when needed and
+/// every line ending in `\n`, including the last
+fn canonical_csv(parts: &[Part]) -> Result<Vec<u8>> {
+    let mut parts: Vec<&Part> = parts.iter().collect();
+    parts.sort_by(|a, b| id_key(a).cmp(&id_key(b)));
+
+    let mut wtr = WriterBuilder::new()
+        .has_headers(false)
+        .quote_style(QuoteStyle::Necessary)
+        .terminator(Terminator::Any(b'\n'))
+        .from_writer(Vec::new());
+    wtr.write_record(CsvRow::HEADERS)?;
+    for part in parts {
+        wtr.serialize(CsvRow::from(part.clone()))?;
+    }
+    wtr.into_inner().map_err(|e| Error::from(e.into_error()))
+}
+
+/// Fail if a category file has columns partman doesn't know, rewriting it
+/// would drop them
+fn check_columns(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let content = fs::read(path)?;
+    let content = content.strip_prefix(UTF8_BOM).unwrap_or(&content);
+    let mut rdr = ReaderBuilder::new().from_reader(content);
+    let unknown: Vec<&str> = rdr.headers()
+        .map_err(|e| Error::csv(path, &CsvRow::HEADERS, e))?
         .iter()
-        .filter_map(|part| part.id.as_deref())
-        .filter_map(|id| {
-            let mut splits = id.split('-');
-            let cat = splits.next()?;
-            if cat == category_code {
-                splits.next()?.parse::<u32>().ok()
-            } else {
-                None
-            }
-        })
-        .max()
-        .unwrap_or(0);
-    Ok(format!("{category_code}-{:04}", max + 1))
-}    
+        .filter(|h| !CsvRow::HEADERS.contains(h))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(Error::Validation(format!(
+            "{} has columns partman doesn't know ({}), remove or rename them before it is rewritten",
+            path.display(),
+            unknown.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Rewrite a category CSV file with the given parts, in canonical form
+fn write_category(parts: &[Part], path: &Path) -> Result<()> {
+    check_columns(path)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, canonical_csv(parts)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_sort_by_number() {
+        let mut parts: Vec<Part> = ["RES-10000", "CAP-0002", "RES-9999", "RES-0001"].iter()
+            .map(|id| Part::for_test(id, "resistor"))
+            .collect();
+        parts.sort_by(|a, b| id_key(a).cmp(&id_key(b)));
+        let ids: Vec<&str> = parts.iter().filter_map(|p| p.id.as_deref()).collect();
+        assert_eq!(ids, ["CAP-0002", "RES-0001", "RES-9999", "RES-10000"]);
+    }
+
+    #[test]
+    fn canonical_form() {
+        let mut note = Part::for_test("RES-0010", "resistor");
+        note.notes = Some("1%, \"thin film\"".into());
+        note.supplier_data.discontinued = Some(false);
+        let parts = [note, Part::for_test("RES-0002", "resistor")];
+
+        let csv = String::from_utf8(canonical_csv(&parts).unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CsvRow::HEADERS.join(","));
+        assert!(lines[1].starts_with("RES-0002,chip,RES-0002 description,,"));
+        assert!(lines[2].contains(r#","1%, ""thin film""","#));
+        assert!(lines[2].contains(",false,"));
+        assert_eq!(lines.len(), 3);
+        assert!(csv.ends_with("\n") && !csv.contains('\r'));
+    }
+
+    #[test]
+    fn reads_spreadsheet_files() {
+        let config = Config::for_test("csv-spreadsheet");
+        let store = CsvStore::new(&config);
+        let path = store.category_path("resistor").unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        let header = CsvRow::HEADERS.map(|h| format!("\"{h}\"")).join(",");
+        let row = |id: &str, discontinued: &str| {
+            let mut fields = vec![String::new(); CsvRow::HEADERS.len()];
+            fields[0] = id.into();
+            fields[1] = "chip".into();
+            fields[19] = discontinued.into();
+            fields.join(",")
+        };
+        let content = format!("\u{feff}{header}\r\n{}\r\n{}\r\n", row("RES-0002", "TRUE"), row("RES-0001", "False"));
+        fs::write(&path, content).unwrap();
+
+        let parts = store.load().unwrap();
+        let discontinued: Vec<Option<bool>> = parts.iter().map(|p| p.supplier_data.discontinued).collect();
+        assert_eq!(discontinued, [Some(true), Some(false)]);
+
+        assert_eq!(store.format(true).unwrap(), std::slice::from_ref(&path));
+        assert_eq!(store.format(false).unwrap(), std::slice::from_ref(&path));
+        assert!(store.format(true).unwrap().is_empty());
+        assert_eq!(fs::read(&path).unwrap(), canonical_csv(&parts).unwrap());
+        fs::remove_dir_all(&config.root).unwrap();
+    }
+
+    #[test]
+    fn never_drops_unknown_columns() {
+        let config = Config::for_test("csv-columns");
+        let store = CsvStore::new(&config);
+        let path = store.category_path("resistor").unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let content = format!("{},Bin\nRES-0001,chip{}A3\n", CsvRow::HEADERS.join(","), ",".repeat(CsvRow::HEADERS.len() - 1));
+        fs::write(&path, &content).unwrap();
+
+        assert_eq!(store.load().unwrap().len(), 1);
+        assert!(store.format(false).is_err());
+        assert!(store.insert(&Part::for_test("RES-0002", "resistor")).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        fs::remove_dir_all(&config.root).unwrap();
+    }
+}
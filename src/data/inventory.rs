@@ -0,0 +1,396 @@
+//! Stock on hand, kept in a CSV file separate from the part library with
+//! one row per part and storage location, and a ledger of every change.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use csv::{ReaderBuilder, WriterBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// Stock of a part in one location
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockEntry {
+    #[serde(rename = "ID")]
+    pub id: String,
+    #[serde(rename = "Location")]
+    pub location: String,
+    #[serde(rename = "Qty")]
+    pub qty: u32,
+    /// Free text lot, date code or reel notes
+    #[serde(rename = "Lot", default)]
+    pub lot: String,
+}
+
+impl StockEntry {
+    const HEADERS: [&str; 4] = ["ID", "Location", "Qty", "Lot"];
+}
+
+/// Stock entries, an empty list if the file doesn't exist yet
+pub fn load_inventory(path: &Path) -> Result<Vec<StockEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut rdr = ReaderBuilder::new().from_path(path)
+        .map_err(|e| Error::csv(path, &StockEntry::HEADERS, e))?;
+    rdr.deserialize()
+        .map(|row| row.map_err(|e| Error::csv(path, &StockEntry::HEADERS, e)))
+        .collect()
+}
+
+/// Rewrite the inventory file sorted by ID and location, dropping empty entries
+pub fn save_inventory(entries: &[StockEntry], path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut entries: Vec<&StockEntry> = entries.iter().filter(|e| e.qty > 0).collect();
+    entries.sort_by(|a, b| (&a.id, &a.location).cmp(&(&b.id, &b.location)));
+
+    let mut wtr = WriterBuilder::new().has_headers(false).from_path(path)?;
+    wtr.write_record(StockEntry::HEADERS)?;
+    for entry in entries {
+        wtr.serialize(entry)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Total quantity and locations of a part
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct StockSummary {
+    pub qty: u32,
+    pub locations: Vec<String>,
+}
+
+/// Stock summed per part ID
+pub fn stock_by_id(entries: &[StockEntry]) -> Result<BTreeMap<String, StockSummary>> {
+    let mut summary: BTreeMap<String, StockSummary> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| e.qty > 0) {
+        let stock = summary.entry(entry.id.clone()).or_default();
+        stock.qty = checked_add(stock.qty, entry.qty, &entry.id, "all locations")?;
+        stock.locations.push(entry.location.clone());
+    }
+    Ok(summary)
+}
+
+fn checked_add(held: u32, qty: u32, id: &str, location: &str) -> Result<u32> {
+    held.checked_add(qty).ok_or_else(|| Error::Validation(format!(
+        "Stock of '{id}' in {location} would be over {}", u32::MAX
+    )))
+}
+
+fn check_qty(qty: u32) -> Result<()> {
+    if qty == 0 {
+        return Err(Error::Validation("Quantity must be at least 1".into()));
+    }
+    Ok(())
+}
+
+fn ledger_entry(time: &str, id: &str, location: &str, change: i64, action: &str) -> LedgerEntry {
+    LedgerEntry {
+        time: time.to_owned(),
+        id: id.to_owned(),
+        location: location.to_owned(),
+        change,
+        action: action.to_owned(),
+        project: String::new(),
+    }
+}
+
+/// Add stock of a part to a location, appending `lot` to the location's lot
+/// notes. Returns the ledger entry for the change.
+pub fn add_stock(
+    entries: &mut Vec<StockEntry>,
+    id: &str,
+    location: &str,
+    qty: u32,
+    lot: Option<&str>,
+    time: &str,
+) -> Result<LedgerEntry> {
+    check_qty(qty)?;
+    match entries.iter_mut().find(|e| e.id == id && e.location == location) {
+        Some(entry) => {
+            entry.qty = checked_add(entry.qty, qty, id, location)?;
+            if let Some(lot) = lot
+                && !entry.lot.split("; ").any(|l| l == lot)
+            {
+                entry.lot = if entry.lot.is_empty() { lot.to_owned() } else { format!("{}; {lot}", entry.lot) };
+            }
+        },
+        None => entries.push(StockEntry {
+            id: id.to_owned(),
+            location: location.to_owned(),
+            qty,
+            lot: lot.unwrap_or_default().to_owned(),
+        }),
+    }
+    Ok(ledger_entry(time, id, location, qty.into(), "add"))
+}
+
+/// Take used stock of a part, from `location` or the only location holding
+/// it. Returns the ledger entry for the change.
+pub fn take_stock(
+    entries: &mut [StockEntry],
+    id: &str,
+    location: Option<&str>,
+    qty: u32,
+    time: &str,
+) -> Result<LedgerEntry> {
+    check_qty(qty)?;
+    let mut held: Vec<&mut StockEntry> = entries.iter_mut()
+        .filter(|e| e.id == id && e.qty > 0)
+        .filter(|e| location.is_none_or(|l| l == e.location))
+        .collect();
+
+    let entry = match held.len() {
+        0 => return Err(Error::Validation(match location {
+            Some(location) => format!("No stock of '{id}' in {location}"),
+            None => format!("No stock of '{id}'"),
+        })),
+        1 => held.remove(0),
+        _ => {
+            let locations: Vec<&str> = held.iter().map(|e| e.location.as_str()).collect();
+            return Err(Error::Validation(format!(
+                "'{id}' is stored in {}, choose one with --location",
+                locations.join(", ")
+            )));
+        },
+    };
+    if entry.qty < qty {
+        return Err(Error::Validation(format!("Only {} of '{id}' in {}", entry.qty, entry.location)));
+    }
+    entry.qty -= qty;
+    Ok(ledger_entry(time, id, &entry.location, -i64::from(qty), "take"))
+}
+
+/// Move stock of a part between locations, everything in `from` unless
+/// `qty` is given. Returns the ledger entries for both locations.
+pub fn move_stock(
+    entries: &mut Vec<StockEntry>,
+    id: &str,
+    from: &str,
+    to: &str,
+    qty: Option<u32>,
+    time: &str,
+) -> Result<[LedgerEntry; 2]> {
+    if let Some(qty) = qty {
+        check_qty(qty)?;
+    }
+    if from == to {
+        return Err(Error::Validation(format!("'{id}' is already in {to}")));
+    }
+    let source = entries.iter()
+        .position(|e| e.id == id && e.location == from && e.qty > 0)
+        .ok_or_else(|| Error::Validation(format!("No stock of '{id}' in {from}")))?;
+    let qty = qty.unwrap_or(entries[source].qty);
+    if entries[source].qty < qty {
+        return Err(Error::Validation(format!("Only {} of '{id}' in {from}", entries[source].qty)));
+    }
+    let lot = entries[source].lot.clone();
+
+    match entries.iter_mut().find(|e| e.id == id && e.location == to) {
+        Some(dest) => {
+            dest.qty = checked_add(dest.qty, qty, id, to)?;
+            if dest.lot.is_empty() {
+                dest.lot = lot;
+            }
+        },
+        None => entries.push(StockEntry {
+            id: id.to_owned(),
+            location: to.to_owned(),
+            qty,
+            lot,
+        }),
+    }
+    entries[source].qty -= qty;
+
+    Ok([
+        ledger_entry(time, id, from, -i64::from(qty), "move"),
+        ledger_entry(time, id, to, qty.into(), "move"),
+    ])
+}
+
+/// File name of the stock levels file, kept next to the category CSVs
+pub const LEVELS_FILE: &str = "stock_levels.csv";
+
+/// Minimum and target stock of a part
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockLevel {
+    #[serde(rename = "ID")]
+    pub id: String,
+    /// Reorder when stock on hand falls below this
+    #[serde(rename = "Min")]
+    pub min: u32,
+    /// Order enough to bring stock on hand up to this
+    #[serde(rename = "Target")]
+    pub target: u32,
+}
+
+impl StockLevel {
+    const HEADERS: [&str; 3] = ["ID", "Min", "Target"];
+}
+
+/// Stock levels, an empty list if the file doesn't exist yet
+pub fn load_levels(path: &Path) -> Result<Vec<StockLevel>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut rdr = ReaderBuilder::new().from_path(path)
+        .map_err(|e| Error::csv(path, &StockLevel::HEADERS, e))?;
+    rdr.deserialize()
+        .map(|row| row.map_err(|e| Error::csv(path, &StockLevel::HEADERS, e)))
+        .collect()
+}
+
+/// Rewrite the stock levels file sorted by ID
+pub fn save_levels(levels: &[StockLevel], path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut levels: Vec<&StockLevel> = levels.iter().collect();
+    levels.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let mut wtr = WriterBuilder::new().has_headers(false).from_path(path)?;
+    wtr.write_record(StockLevel::HEADERS)?;
+    for level in levels {
+        wtr.serialize(level)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// A stock change, appended to the ledger by every stock command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// UTC time as `YYYY-MM-DD HH:MM`
+    #[serde(rename = "Time")]
+    pub time: String,
+    #[serde(rename = "ID")]
+    pub id: String,
+    #[serde(rename = "Location")]
+    pub location: String,
+    /// Quantity added, negative when taken
+    #[serde(rename = "Change")]
+    pub change: i64,
+    /// `add`, `take`, `move` or `kit`
+    #[serde(rename = "Action")]
+    pub action: String,
+    /// Project a kit was built for
+    #[serde(rename = "Project", default)]
+    pub project: String,
+}
+
+impl LedgerEntry {
+    const HEADERS: [&str; 6] = ["Time", "ID", "Location", "Change", "Action", "Project"];
+}
+
+/// Ledger entries in the order they were recorded, an empty list if the
+/// file doesn't exist yet
+pub fn load_ledger(path: &Path) -> Result<Vec<LedgerEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut rdr = ReaderBuilder::new().from_path(path)
+        .map_err(|e| Error::csv(path, &LedgerEntry::HEADERS, e))?;
+    rdr.deserialize()
+        .map(|row| row.map_err(|e| Error::csv(path, &LedgerEntry::HEADERS, e)))
+        .collect()
+}
+
+/// Append entries to the ledger, creating it with headers if needed
+pub fn append_ledger(entries: &[LedgerEntry], path: &Path) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let exists = path.exists();
+    let file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+
+    let mut wtr = WriterBuilder::new().has_headers(false).from_writer(file);
+    if !exists {
+        wtr.write_record(LedgerEntry::HEADERS)?;
+    }
+    for entry in entries {
+        wtr.serialize(entry)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIME: &str = "2026-01-01 00:00";
+
+    fn entry(id: &str, location: &str, qty: u32, lot: &str) -> StockEntry {
+        StockEntry { id: id.into(), location: location.into(), qty, lot: lot.into() }
+    }
+
+    fn held(entries: &[StockEntry]) -> Vec<(&str, &str, u32, &str)> {
+        entries.iter().map(|e| (e.id.as_str(), e.location.as_str(), e.qty, e.lot.as_str())).collect()
+    }
+
+    #[test]
+    fn adds_stock_and_lots() {
+        let mut entries = vec![entry("RES-0001", "A1", 10, "")];
+        let change = add_stock(&mut entries, "RES-0001", "A1", 5, Some("2231"), TIME).unwrap();
+        assert_eq!((change.change, change.action.as_str()), (5, "add"));
+        add_stock(&mut entries, "RES-0001", "A1", 5, Some("2231"), TIME).unwrap();
+        add_stock(&mut entries, "RES-0001", "A1", 1, Some("2302"), TIME).unwrap();
+        add_stock(&mut entries, "RES-0001", "B2", 100, None, TIME).unwrap();
+        assert_eq!(held(&entries), [("RES-0001", "A1", 21, "2231; 2302"), ("RES-0001", "B2", 100, "")]);
+
+        assert!(add_stock(&mut entries, "RES-0001", "A1", 0, None, TIME).is_err());
+        assert!(add_stock(&mut entries, "RES-0001", "A1", u32::MAX, None, TIME).is_err());
+        assert_eq!(entries[0].qty, 21);
+    }
+
+    #[test]
+    fn takes_stock_from_the_only_location() {
+        let mut entries = vec![entry("RES-0001", "A1", 10, ""), entry("RES-0001", "empty", 0, "")];
+        let change = take_stock(&mut entries, "RES-0001", None, 4, TIME).unwrap();
+        assert_eq!((change.location.as_str(), change.change), ("A1", -4));
+        assert!(take_stock(&mut entries, "RES-0001", None, 7, TIME).is_err());
+        assert!(take_stock(&mut entries, "RES-0001", None, 0, TIME).is_err());
+        assert!(take_stock(&mut entries, "RES-0002", None, 1, TIME).is_err());
+        assert_eq!(entries[0].qty, 6);
+
+        entries.push(entry("RES-0001", "B2", 3, ""));
+        assert!(take_stock(&mut entries, "RES-0001", None, 1, TIME).is_err());
+        take_stock(&mut entries, "RES-0001", Some("B2"), 3, TIME).unwrap();
+        assert_eq!(entries[2].qty, 0);
+    }
+
+    #[test]
+    fn moves_stock_between_locations() {
+        let mut entries = vec![entry("RES-0001", "A1", 10, "2231"), entry("RES-0001", "B2", 1, "")];
+        let changes = move_stock(&mut entries, "RES-0001", "A1", "B2", Some(4), TIME).unwrap();
+        assert_eq!(changes.map(|c| c.change), [-4, 4]);
+        move_stock(&mut entries, "RES-0001", "A1", "C3", None, TIME).unwrap();
+        assert_eq!(held(&entries), [
+            ("RES-0001", "A1", 0, "2231"),
+            ("RES-0001", "B2", 5, "2231"),
+            ("RES-0001", "C3", 6, "2231"),
+        ]);
+
+        assert!(move_stock(&mut entries, "RES-0001", "A1", "B2", None, TIME).is_err());
+        assert!(move_stock(&mut entries, "RES-0001", "B2", "C3", Some(0), TIME).is_err());
+        assert!(move_stock(&mut entries, "RES-0001", "B2", "C3", Some(6), TIME).is_err());
+        assert!(move_stock(&mut entries, "RES-0001", "B2", "B2", None, TIME).is_err());
+        entries[2].qty = u32::MAX;
+        assert!(move_stock(&mut entries, "RES-0001", "B2", "C3", None, TIME).is_err());
+        assert_eq!(entries[1].qty, 5);
+    }
+
+    #[test]
+    fn sums_stock_per_part() {
+        let entries = [entry("RES-0001", "A1", 10, ""), entry("RES-0001", "B2", 5, ""), entry("CAP-0001", "A1", 0, "")];
+        let stock = stock_by_id(&entries).unwrap();
+        assert_eq!(stock.len(), 1);
+        assert_eq!((stock["RES-0001"].qty, stock["RES-0001"].locations.len()), (15, 2));
+        assert!(stock_by_id(&[entry("RES-0001", "A1", u32::MAX, ""), entry("RES-0001", "B2", 1, "")]).is_err());
+    }
+}
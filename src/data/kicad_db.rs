@@ -1,95 +1,454 @@
-use std::{collections::HashMap, path::Path};
-use rusqlite::{params, Connection};
+use std::{collections::{BTreeMap, HashMap, HashSet}, path::PathBuf};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::Serialize;
 use std::fs;
-use crate::{config::Cat, part::Part, Result};
+use crate::{config::{Config, DatasheetLink}, part::Part, util::sha256_hex, Result};
+use super::inventory::{load_inventory, stock_by_id, StockSummary};
 
+/// Table recording what each category table was built from
+const META_TABLE: &str = "_partman_meta";
 
-pub fn build_kicad_db(kicad_db_path: &Path, parts: Vec<Part>, categories: &HashMap<String, Cat> ) -> Result<()>{
+/// Metadata row for the optional objects spanning every category
+const EXTRAS_KEY: &str = "_extras";
 
-    if kicad_db_path.exists() {
-        fs::remove_file(kicad_db_path)?;
-    } else if let Some(parent_dir) = kicad_db_path.parent() {
-        fs::create_dir_all(parent_dir)?;
+/// FTS5 table over every category, see `[db] search_index`
+const SEARCH_TABLE: &str = "parts_fts";
+
+/// View of every category, see `[db] all_parts_view`
+const ALL_PARTS_VIEW: &str = "all_parts";
+
+/// Names of partman's own DB objects, which categories can't use
+pub const RESERVED_TABLES: &[&str] = &[META_TABLE, EXTRAS_KEY, SEARCH_TABLE, ALL_PARTS_VIEW];
+
+/// Summary of a KiCad DB build
+#[derive(Debug, Serialize)]
+pub struct BuildReport {
+    pub db_path: PathBuf,
+    /// Number of rows in each category table
+    pub tables: BTreeMap<String, usize>,
+    /// Tables left untouched because their parts and schema had not changed
+    pub unchanged: Vec<String>,
+    /// Tables dropped because their category was removed from the config
+    pub removed: Vec<String>,
+}
+
+/// Datasheet column value, the downloaded file when configured and available
+fn datasheet_link(part: &Part, config: &Config) -> String {
+    if config.datasheets.db_link == DatasheetLink::Local
+        && let Some(file) = &part.datasheet_file
+    {
+        let path = config.datasheets.dir_path.join(file);
+        if path.exists() {
+            return path.display().to_string();
+        }
+    }
+    part.datasheet.clone().unwrap_or_default()
+}
+
+/// Hash of what a category table is filled from. The parts are hashed rather
+/// than the CSV file so every storage backend works, along with the resolved
+/// datasheet links since those depend on which downloaded files exist.
+fn parts_hash(parts: &[&Part], config: &Config) -> Result<String> {
+    #[derive(Serialize)]
+    struct Row<'a> {
+        part: &'a Part,
+        datasheet: String,
+    }
+    let rows: Vec<Row> = parts.iter()
+        .map(|&part| Row { part, datasheet: datasheet_link(part, config) })
+        .collect();
+    Ok(sha256_hex(&serde_json::to_vec(&rows)?))
+}
+
+/// Hash of the stock columns of a category table, empty when the DB has none
+fn stock_hash(parts: &[&Part], stock: Option<&BTreeMap<String, StockSummary>>) -> Result<String> {
+    let Some(stock) = stock else {
+        return Ok(String::new());
+    };
+    let rows: Vec<(&str, Option<&StockSummary>)> = parts.iter()
+        .filter_map(|part| part.id.as_deref())
+        .map(|id| (id, stock.get(id)))
+        .collect();
+    Ok(sha256_hex(&serde_json::to_vec(&rows)?))
+}
+
+fn create_table_sql(category_name: &str, with_stock: bool) -> String {
+    let stock_columns = if with_stock { ",\n            StockQty INTEGER,\n            StockLocation TEXT" } else { "" };
+    format!(
+        "CREATE TABLE {} (
+            id TEXT PRIMARY KEY,
+            Subcategory TEXT,
+            Description TEXT,
+            Value TEXT,
+            Manufacturer TEXT,
+            MPN TEXT,
+            Package TEXT,
+            Supplier1 TEXT,
+            SPN1 TEXT,
+            Supplier2 TEXT,
+            SPN2 TEXT,
+            Symbol TEXT,
+            Footprint TEXT,
+            Datasheet TEXT,
+            Status TEXT,
+            Notes TEXT,
+            UnitPrice REAL,
+            QtyAvailable INTEGER,
+            ProductStatus TEXT,
+            Discontinued INTEGER,
+            EndOfLife INTEGER,
+            LastChecked TEXT,
+            JlcType TEXT,
+            DatasheetFile TEXT,
+            DatasheetSha256 TEXT{}
+        )",
+        category_name, stock_columns
+    )
+}
+
+fn insert_sql(category_name: &str) -> String {
+    format!(
+        "INSERT INTO {} (\r
+            id, Subcategory, Description, Value, Manufacturer, MPN, Package,\r
+            Supplier1, SPN1, Supplier2, SPN2,\r
+            Symbol, Footprint, Datasheet, Status, Notes,\r
+            UnitPrice, QtyAvailable, ProductStatus, Discontinued, EndOfLife, LastChecked,\r
+            JlcType, DatasheetFile, DatasheetSha256\r
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,\r
+            ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25)",
+        category_name
+    )
+}
+
+/// Hash of everything other than the parts that affects a table's contents,
+/// the table definition, its indexes and how datasheets are linked
+fn schema_hash(category_name: &str, config: &Config) -> String {
+    let schema = format!(
+        "{}\n{}\n{:?}\n{}",
+        create_table_sql(category_name, config.inventory.db_columns),
+        config.db.indexes,
+        config.datasheets.db_link,
+        config.datasheets.dir_path.display(),
+    );
+    sha256_hex(schema.as_bytes())
+}
+
+fn create_indexes(tx: &Transaction, category_name: &str) -> Result<()> {
+    for column in ["MPN", "SPN1", "SPN2"] {
+        tx.execute(
+            &format!("CREATE INDEX {category_name}_{} ON {category_name} ({column})", column.to_lowercase()),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// Recreate the search table and view over every category table
+fn build_extras(tx: &Transaction, names: &[&String], config: &Config) -> Result<()> {
+    tx.execute(&format!("DROP VIEW IF EXISTS {ALL_PARTS_VIEW}"), [])?;
+    tx.execute(&format!("DROP TABLE IF EXISTS {SEARCH_TABLE}"), [])?;
+    if names.is_empty() {
+        return Ok(());
+    }
+
+    if config.db.all_parts_view {
+        let selects: Vec<String> = names.iter()
+            .map(|name| format!("SELECT '{name}' AS category, * FROM {name}"))
+            .collect();
+        tx.execute(
+            &format!("CREATE VIEW {ALL_PARTS_VIEW} AS {}", selects.join(" UNION ALL ")),
+            [],
+        )?;
     }
 
+    if config.db.search_index {
+        tx.execute(
+            &format!(
+                "CREATE VIRTUAL TABLE {SEARCH_TABLE} USING fts5(
+                    id UNINDEXED, category UNINDEXED, Description, Value, MPN, Manufacturer, Notes
+                )"
+            ),
+            [],
+        )?;
+        for name in names {
+            tx.execute(
+                &format!(
+                    "INSERT INTO {SEARCH_TABLE} (id, category, Description, Value, MPN, Manufacturer, Notes)
+                     SELECT id, '{name}', Description, Value, MPN, Manufacturer, Notes FROM {name}"
+                ),
+                [],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Fill the stock columns of a table
+fn update_stock(tx: &Transaction, category_name: &str, parts: &[&Part], stock: &BTreeMap<String, StockSummary>) -> Result<()> {
+    let mut stmt = tx.prepare(&format!(
+        "UPDATE {category_name} SET StockQty = ?1, StockLocation = ?2 WHERE id = ?3"
+    ))?;
+    for part in parts {
+        let Some(id) = &part.id else { continue };
+        let summary = stock.get(id).cloned().unwrap_or_default();
+        stmt.execute(params![summary.qty, summary.locations.join(", "), id])?;
+    }
+    Ok(())
+}
+
+fn insert_parts(tx: &Transaction, category_name: &str, parts: &[&Part], config: &Config) -> Result<()> {
+    let mut stmt = tx.prepare(&insert_sql(category_name))?;
+    for part in parts {
+        stmt.execute(params![
+            part.id.as_deref().unwrap_or(""),
+            part.subcategory,
+            part.description,
+            part.value,
+            part.manufacturer.as_deref().unwrap_or_default(),
+            part.mpn.as_deref().unwrap_or_default(),
+            part.package.as_deref().unwrap_or_default(),
+            part.supplier1.as_deref().unwrap_or_default(),
+            part.spn1.as_deref().unwrap_or_default(),
+            part.supplier2.as_deref().unwrap_or_default(),
+            part.spn2.as_deref().unwrap_or_default(),
+            part.symbol,
+            part.footprint.as_deref().unwrap_or_default(),
+            datasheet_link(part, config),
+            part.status,
+            part.notes.as_deref().unwrap_or_default(),
+            part.supplier_data.unit_price,
+            part.supplier_data.qty_available,
+            part.supplier_data.product_status,
+            part.supplier_data.discontinued,
+            part.supplier_data.end_of_life,
+            part.supplier_data.last_checked,
+            part.jlc_type,
+            part.datasheet_file,
+            part.datasheet_sha256,
+        ])?;
+    }
+    Ok(())
+}
+
+/// Delete the DB file and build every table from scratch
+pub fn rebuild_kicad_db(parts: Vec<Part>, config: &Config) -> Result<BuildReport> {
+    if config.db_file_path.exists() {
+        fs::remove_file(&config.db_file_path)?;
+    }
+    build_kicad_db(parts, config)
+}
+
+/// Update the DB, only dropping and refilling tables whose parts or table
+/// schema changed since the last build. Each table's parts, stock and schema
+/// hashes are stored in the `_partman_meta` table inside the DB.
+pub fn build_kicad_db(parts: Vec<Part>, config: &Config) -> Result<BuildReport>{
+    let kicad_db_path = &config.db_file_path;
+    let categories = &config.cat;
+
+    if let Some(parent_dir) = kicad_db_path.parent() {
+        fs::create_dir_all(parent_dir)?;
+    }
 
     let mut conn = Connection::open(kicad_db_path)?;
     conn.execute_batch(
         "
-        PRAGMA journal_mode = OFF;
         PRAGMA synchronous = OFF;
         PRAGMA cache_size = 1000000;
-        PRAGMA locking_mode = EXCLUSIVE;
         PRAGMA temp_store = MEMORY;
         "
     )?;
 
-    for category_name in categories.keys() {
-
-        let create_sql = format!(
-            "CREATE TABLE {} (
-                id TEXT PRIMARY KEY,
-                Subcategory TEXT,
-                Description TEXT,
-                Value TEXT,
-                Manufacturer TEXT,
-                MPN TEXT,
-                Package TEXT,
-                Supplier1 TEXT,
-                SPN1 TEXT,
-                Supplier2 TEXT,
-                SPN2 TEXT,
-                Symbol TEXT,
-                Footprint TEXT,
-                Datasheet TEXT,
-                Status TEXT,
-                Notes TEXT
-            )",
-            category_name
-        );
-        conn.execute(&create_sql, [])?;
+    let stock = if config.inventory.db_columns {
+        Some(stock_by_id(&load_inventory(&config.inventory.file_path)?)?)
+    } else {
+        None
+    };
+
+    let mut by_category: HashMap<&str, Vec<&Part>> = HashMap::new();
+    for part in &parts {
+        by_category.entry(part.category.as_str()).or_default().push(part);
     }
 
+    let mut report = BuildReport {
+        db_path: kicad_db_path.to_path_buf(),
+        tables: BTreeMap::new(),
+        unchanged: Vec::new(),
+        removed: Vec::new(),
+    };
+
     let tx = conn.transaction()?;
-    {
-        let mut insert_statements = HashMap::new();
-        for category_name in categories.keys() {
-            let insert_sql = format!(
-                "INSERT INTO {} (\r
-                    id, Subcategory, Description, Value, Manufacturer, MPN, Package,\r
-                    Supplier1, SPN1, Supplier2, SPN2,\r
-                    Symbol, Footprint, Datasheet, Status, Notes\r
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
-                category_name
-            );
-            insert_statements.insert(category_name, tx.prepare(&insert_sql)?);
+    // The DB is build output, a meta table from another layout only means
+    // every table gets rebuilt
+    let meta_current = tx.query_row(
+        &format!("SELECT 1 FROM pragma_table_info('{META_TABLE}') WHERE name = 'parts_sha256'"),
+        [],
+        |_| Ok(()),
+    ).optional()?.is_some();
+    if !meta_current {
+        tx.execute(&format!("DROP TABLE IF EXISTS {META_TABLE}"), [])?;
+    }
+    tx.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {META_TABLE} (
+                name TEXT PRIMARY KEY,
+                parts_sha256 TEXT NOT NULL,
+                stock_sha256 TEXT NOT NULL DEFAULT '',
+                schema_sha256 TEXT NOT NULL
+            )"
+        ),
+        [],
+    )?;
+
+    let mut names: Vec<&String> = categories.keys().collect();
+    names.sort();
+    for &category_name in &names {
+        let category_parts = by_category.get(category_name.as_str()).map(Vec::as_slice).unwrap_or_default();
+        let parts_hash = parts_hash(category_parts, config)?;
+        // Stock columns also change when the inventory does
+        let stock_hash = stock_hash(category_parts, stock.as_ref())?;
+        let schema_hash = schema_hash(category_name, config);
+        report.tables.insert(category_name.clone(), category_parts.len());
+
+        let built: Option<(String, String, String)> = tx.query_row(
+            &format!("SELECT parts_sha256, stock_sha256, schema_sha256 FROM {META_TABLE} WHERE name = ?1"),
+            [category_name],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).optional()?;
+        let table_exists = tx.query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [category_name],
+            |_| Ok(()),
+        ).optional()?.is_some();
+
+        if table_exists && built == Some((parts_hash.clone(), stock_hash.clone(), schema_hash.clone())) {
+            report.unchanged.push(category_name.clone());
+            continue;
         }
-        for part in parts {
-            if let Some(stmt) = insert_statements.get_mut(&part.category) {
-                stmt.execute(params![
-                    part.id.as_deref().unwrap_or(""),
-                    part.subcategory,
-                    part.description,
-                    part.value,
-                    part.manufacturer.unwrap_or_default(),
-                    part.mpn.unwrap_or_default(),
-                    part.package.unwrap_or_default(),
-                    part.supplier1.unwrap_or_default(),
-                    part.spn1.unwrap_or_default(),
-                    part.supplier2.unwrap_or_default(),
-                    part.spn2.unwrap_or_default(),
-                    part.symbol,
-                    part.footprint.unwrap_or_default(),
-                    part.datasheet.unwrap_or_default(),
-                    part.status,
-                    part.notes.unwrap_or_default(),
-                ])?;
-            }
+
+        tx.execute(&format!("DROP TABLE IF EXISTS {category_name}"), [])?;
+        tx.execute(&create_table_sql(category_name, stock.is_some()), [])?;
+        insert_parts(&tx, category_name, category_parts, config)?;
+        if let Some(stock) = &stock {
+            update_stock(&tx, category_name, category_parts, stock)?;
+        }
+        if config.db.indexes {
+            create_indexes(&tx, category_name)?;
         }
+        tx.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {META_TABLE} (name, parts_sha256, stock_sha256, schema_sha256) VALUES (?1, ?2, ?3, ?4)"
+            ),
+            params![category_name, parts_hash, stock_hash, schema_hash],
+        )?;
+    }
+
+    // Drop tables of categories that were removed from the config
+    let built_names: Vec<String> = tx
+        .prepare(&format!("SELECT name FROM {META_TABLE} WHERE name != ?1"))?
+        .query_map([EXTRAS_KEY], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    let configured: HashSet<&String> = categories.keys().collect();
+    for name in built_names.into_iter().filter(|n| !configured.contains(n)) {
+        tx.execute(&format!("DROP TABLE IF EXISTS {name}"), [])?;
+        tx.execute(&format!("DELETE FROM {META_TABLE} WHERE name = ?1"), [&name])?;
+        report.removed.push(name);
+    }
+
+    // The search table and view span every category, so any table change
+    // or a change of the `[db]` settings rebuilds them
+    let extras_hash = sha256_hex(format!("{:?}\n{names:?}", config.db).as_bytes());
+    let extras_built: Option<String> = tx.query_row(
+        &format!("SELECT schema_sha256 FROM {META_TABLE} WHERE name = ?1"),
+        [EXTRAS_KEY],
+        |row| row.get(0),
+    ).optional()?;
+    let tables_changed = report.unchanged.len() != names.len() || !report.removed.is_empty();
+    if tables_changed || extras_built.as_ref() != Some(&extras_hash) {
+        build_extras(&tx, &names, config)?;
+        tx.execute(
+            &format!("INSERT OR REPLACE INTO {META_TABLE} (name, parts_sha256, schema_sha256) VALUES (?1, '', ?2)"),
+            params![EXTRAS_KEY, extras_hash],
+        )?;
     }
     tx.commit()?;
 
-    println!("Successfully built KiCad DB: {}", kicad_db_path.display());
+    Ok(report)
+}
 
-    Ok(())
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{save_inventory, StockEntry};
+
+    fn parts() -> Vec<Part> {
+        vec![Part::for_test("RES-0001", "resistor"), Part::for_test("CAP-0001", "capacitor")]
+    }
+
+    fn rebuilt(report: &BuildReport) -> Vec<&str> {
+        report.tables.keys().filter(|t| !report.unchanged.contains(t)).map(String::as_str).collect()
+    }
+
+    fn value(config: &Config, table: &str, id: &str) -> String {
+        let conn = Connection::open(&config.db_file_path).unwrap();
+        conn.query_row(&format!("SELECT Value FROM {table} WHERE ID = ?1"), [id], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn rebuilds_only_changed_tables() {
+        let mut config = Config::for_test("db-incremental");
+        let report = build_kicad_db(parts(), &config).unwrap();
+        assert_eq!(rebuilt(&report), ["capacitor", "resistor"]);
+        assert!(rebuilt(&build_kicad_db(parts(), &config).unwrap()).is_empty());
+
+        let mut changed = parts();
+        changed[0].value = "10k".into();
+        assert_eq!(rebuilt(&build_kicad_db(changed.clone(), &config).unwrap()), ["resistor"]);
+        assert_eq!(value(&config, "resistor", "RES-0001"), "10k");
+
+        // A downloaded datasheet changes the link without changing the part
+        config.datasheets.db_link = DatasheetLink::Local;
+        let report = build_kicad_db(changed.clone(), &config).unwrap();
+        assert_eq!(rebuilt(&report), ["capacitor", "resistor"]);
+        changed[1].datasheet_file = Some("CAP-0001.pdf".into());
+        assert_eq!(rebuilt(&build_kicad_db(changed.clone(), &config).unwrap()), ["capacitor"]);
+        fs::create_dir_all(&config.datasheets.dir_path).unwrap();
+        fs::write(config.datasheets.dir_path.join("CAP-0001.pdf"), b"%PDF-1.7").unwrap();
+        assert_eq!(rebuilt(&build_kicad_db(changed.clone(), &config).unwrap()), ["capacitor"]);
+
+        config.cat.remove("capacitor");
+        let report = build_kicad_db(changed, &config).unwrap();
+        assert_eq!(report.removed, ["capacitor"]);
+        assert!(rebuilt(&report).is_empty());
+        fs::remove_dir_all(&config.root).unwrap();
+    }
+
+    #[test]
+    fn stock_changes_rebuild_only_their_table() {
+        let mut config = Config::for_test("db-stock");
+        config.inventory.db_columns = true;
+        build_kicad_db(parts(), &config).unwrap();
+
+        let stock = [StockEntry { id: "CAP-0001".into(), location: "A1".into(), qty: 10, lot: String::new() }];
+        save_inventory(&stock, &config.inventory.file_path).unwrap();
+        assert_eq!(rebuilt(&build_kicad_db(parts(), &config).unwrap()), ["capacitor"]);
+        assert!(rebuilt(&build_kicad_db(parts(), &config).unwrap()).is_empty());
+        fs::remove_dir_all(&config.root).unwrap();
+    }
+
+    #[test]
+    fn full_and_stale_builds_rebuild_everything() {
+        let config = Config::for_test("db-full");
+        build_kicad_db(parts(), &config).unwrap();
+        assert_eq!(rebuilt(&rebuild_kicad_db(parts(), &config).unwrap()), ["capacitor", "resistor"]);
+
+        // A meta table in another layout is replaced rather than read
+        let conn = Connection::open(&config.db_file_path).unwrap();
+        conn.execute_batch(&format!(
+            "DROP TABLE {META_TABLE}; CREATE TABLE {META_TABLE} (name TEXT PRIMARY KEY, csv_sha256 TEXT NOT NULL)"
+        )).unwrap();
+        drop(conn);
+        assert_eq!(rebuilt(&build_kicad_db(parts(), &config).unwrap()), ["capacitor", "resistor"]);
+        assert!(rebuilt(&build_kicad_db(parts(), &config).unwrap()).is_empty());
+        fs::remove_dir_all(&config.root).unwrap();
+    }
+}
@@ -1,5 +1,14 @@
 
 mod kicad_db;
 mod csv_store;
-pub use kicad_db::build_kicad_db;
-pub use csv_store::{ insert_part, load_all_parts, get_next_id};
\ No newline at end of file
+mod sqlite_store;
+mod store;
+mod inventory;
+pub use kicad_db::{build_kicad_db, rebuild_kicad_db, BuildReport, RESERVED_TABLES};
+pub use inventory::{
+    add_stock, append_ledger, load_inventory, load_ledger, load_levels, move_stock, save_inventory, save_levels,
+    stock_by_id, take_stock, LedgerEntry, StockEntry, StockLevel, StockSummary, LEVELS_FILE,
+};
+pub use csv_store::CsvStore;
+pub use sqlite_store::SqliteStore;
+pub use store::{open_backend, open_store, PartStore};
\ No newline at end of file
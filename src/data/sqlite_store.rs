@@ -0,0 +1,292 @@
+//! Parts kept in a single SQLite database, one `parts` table with a row per
+//! part. This is partman's own canonical store, the KiCad DB is still built
+//! from it separately.
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::{
+    config::Config,
+    part::{Part, SupplierData},
+    Error, Result,
+};
+
+use super::{store::{next_id_in, no_part}, PartStore};
+
+/// Schema version kept in `PRAGMA user_version`
+const SCHEMA_VERSION: i32 = 1;
+
+const CREATE_SQL: &str = "
+    CREATE TABLE IF NOT EXISTS parts (
+        ID TEXT PRIMARY KEY,
+        Category TEXT NOT NULL,
+        Subcategory TEXT NOT NULL,
+        Description TEXT NOT NULL,
+        Value TEXT NOT NULL,
+        Manufacturer TEXT,
+        MPN TEXT,
+        Package TEXT,
+        Supplier1 TEXT,
+        SPN1 TEXT,
+        Supplier2 TEXT,
+        SPN2 TEXT,
+        Symbol TEXT NOT NULL,
+        Footprint TEXT,
+        Datasheet TEXT,
+        Status TEXT NOT NULL,
+        Notes TEXT,
+        UnitPrice REAL,
+        QtyAvailable INTEGER,
+        ProductStatus TEXT,
+        Discontinued INTEGER,
+        EndOfLife INTEGER,
+        LastChecked TEXT,
+        JlcType TEXT,
+        DatasheetFile TEXT,
+        DatasheetSha256 TEXT,
+        DatasheetSource TEXT
+    );
+    CREATE INDEX IF NOT EXISTS parts_category ON parts (Category);
+";
+
+const COLUMNS: &str = "
+    ID, Category, Subcategory, Description, Value, Manufacturer, MPN, Package,
+    Supplier1, SPN1, Supplier2, SPN2,
+    Symbol, Footprint, Datasheet, Status, Notes,
+    UnitPrice, QtyAvailable, ProductStatus, Discontinued, EndOfLife, LastChecked,
+    JlcType, DatasheetFile, DatasheetSha256, DatasheetSource
+";
+
+/// Parts kept in the database at `[storage] sqlite_path`
+pub struct SqliteStore<'a> {
+    config: &'a Config,
+    conn: Connection,
+}
+
+impl<'a> SqliteStore<'a> {
+    /// Open the database, creating it if it doesn't exist
+    pub fn open(config: &'a Config) -> Result<Self> {
+        let path = &config.storage.sqlite_path;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+
+        let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(Error::Validation(format!(
+                "{} was written by a newer partman (schema {version}), upgrade partman to use it",
+                path.display()
+            )));
+        }
+        conn.execute_batch(CREATE_SQL)?;
+        conn.execute_batch(&format!("PRAGMA user_version = {SCHEMA_VERSION}"))?;
+
+        Ok(Self { config, conn })
+    }
+
+    fn check_category(&self, category: &str) -> Result<()> {
+        if !self.config.cat.contains_key(category) {
+            return Err(Error::Validation(format!("Invalid category '{category}'")));
+        }
+        Ok(())
+    }
+}
+
+fn insert(conn: &Connection, part: &Part) -> Result<()> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "INSERT INTO parts ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13,
+            ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27)"
+    ))?;
+    stmt.execute(params![
+        part.id,
+        part.category,
+        part.subcategory,
+        part.description,
+        part.value,
+        part.manufacturer,
+        part.mpn,
+        part.package,
+        part.supplier1,
+        part.spn1,
+        part.supplier2,
+        part.spn2,
+        part.symbol,
+        part.footprint,
+        part.datasheet,
+        part.status,
+        part.notes,
+        part.supplier_data.unit_price,
+        part.supplier_data.qty_available,
+        part.supplier_data.product_status,
+        part.supplier_data.discontinued,
+        part.supplier_data.end_of_life,
+        part.supplier_data.last_checked,
+        part.jlc_type,
+        part.datasheet_file,
+        part.datasheet_sha256,
+        part.datasheet_source,
+    ])?;
+    Ok(())
+}
+
+fn part_from_row(row: &Row) -> rusqlite::Result<Part> {
+    Ok(Part {
+        id: row.get("ID")?,
+        category: row.get("Category")?,
+        subcategory: row.get("Subcategory")?,
+        description: row.get("Description")?,
+        value: row.get("Value")?,
+        manufacturer: row.get("Manufacturer")?,
+        mpn: row.get("MPN")?,
+        package: row.get("Package")?,
+        supplier1: row.get("Supplier1")?,
+        spn1: row.get("SPN1")?,
+        supplier2: row.get("Supplier2")?,
+        spn2: row.get("SPN2")?,
+        symbol: row.get("Symbol")?,
+        footprint: row.get("Footprint")?,
+        datasheet: row.get("Datasheet")?,
+        status: row.get("Status")?,
+        notes: row.get("Notes")?,
+        supplier_data: SupplierData {
+            unit_price: row.get("UnitPrice")?,
+            qty_available: row.get("QtyAvailable")?,
+            product_status: row.get("ProductStatus")?,
+            discontinued: row.get("Discontinued")?,
+            end_of_life: row.get("EndOfLife")?,
+            last_checked: row.get("LastChecked")?,
+        },
+        jlc_type: row.get("JlcType")?,
+        datasheet_file: row.get("DatasheetFile")?,
+        datasheet_sha256: row.get("DatasheetSha256")?,
+        datasheet_source: row.get("DatasheetSource")?,
+    })
+}
+
+impl PartStore for SqliteStore<'_> {
+    fn load(&self) -> Result<Vec<Part>> {
+        // Row order follows insertion like appending to a CSV file
+        let mut stmt = self.conn.prepare(&format!("SELECT {COLUMNS} FROM parts ORDER BY Category, rowid"))?;
+        let parts = stmt.query_map([], part_from_row)?.collect::<rusqlite::Result<_>>()?;
+        Ok(parts)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Part>> {
+        let part = self.conn
+            .query_row(&format!("SELECT {COLUMNS} FROM parts WHERE ID = ?1"), [id], part_from_row)
+            .optional()?;
+        Ok(part)
+    }
+
+    fn insert(&self, part: &Part) -> Result<()> {
+        self.check_category(&part.category)?;
+        insert(&self.conn, part)
+    }
+
+    fn update(&self, part: &Part) -> Result<()> {
+        self.check_category(&part.category)?;
+        let id = part.id.as_deref().unwrap_or_default();
+        let tx = self.conn.unchecked_transaction()?;
+        if tx.execute("DELETE FROM parts WHERE ID = ?1", [id])? == 0 {
+            return Err(no_part(id));
+        }
+        insert(&tx, part)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> Result<Part> {
+        let part = self.get(id)?.ok_or_else(|| no_part(id))?;
+        self.conn.execute("DELETE FROM parts WHERE ID = ?1", [id])?;
+        Ok(part)
+    }
+
+    fn next_id(&self, category_code: &str) -> Result<String> {
+        let mut stmt = self.conn.prepare("SELECT ID FROM parts WHERE ID LIKE ?1 || '-%'")?;
+        let ids: Vec<String> = stmt
+            .query_map([category_code], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(next_id_in(ids.iter().map(String::as_str), category_code))
+    }
+
+    fn save_categories(&self, parts: &[Part], categories: &[&str]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for &category in categories {
+            self.check_category(category)?;
+            tx.execute("DELETE FROM parts WHERE Category = ?1", [category])?;
+            for part in parts.iter().filter(|p| p.category == category) {
+                insert(&tx, part)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn ids(parts: &[Part]) -> Vec<&str> {
+        parts.iter().filter_map(|p| p.id.as_deref()).collect()
+    }
+
+    #[test]
+    fn stores_parts() {
+        let config = Config::for_test("sqlite-crud");
+        let store = SqliteStore::open(&config).unwrap();
+
+        let mut part = Part::for_test("RES-0001", "resistor");
+        part.mpn = Some("RC0402FR-07120KL".into());
+        part.supplier_data.unit_price = Some(0.012);
+        part.supplier_data.end_of_life = Some(true);
+        store.insert(&part).unwrap();
+        store.insert(&Part::for_test("RES-0009", "resistor")).unwrap();
+        store.insert(&Part::for_test("CAP-0001", "capacitor")).unwrap();
+        assert!(store.insert(&Part::for_test("IND-0001", "inductor")).is_err());
+        assert!(store.insert(&part).is_err());
+
+        let loaded = store.get("RES-0001").unwrap().unwrap();
+        assert_eq!(loaded.mpn, part.mpn);
+        assert_eq!(loaded.supplier_data, part.supplier_data);
+        assert_eq!(store.next_id("RES").unwrap(), "RES-0010");
+        assert_eq!(store.next_id("IND").unwrap(), "IND-0001");
+
+        part.category = "capacitor".into();
+        part.value = "100n".into();
+        store.update(&part).unwrap();
+        assert_eq!(store.get("RES-0001").unwrap().unwrap().category, "capacitor");
+        assert!(store.update(&Part::for_test("RES-0404", "resistor")).is_err());
+
+        assert_eq!(store.delete("RES-0009").unwrap().id.as_deref(), Some("RES-0009"));
+        assert!(store.delete("RES-0009").is_err());
+        assert_eq!(ids(&store.load().unwrap()), ["CAP-0001", "RES-0001"]);
+        fs::remove_dir_all(&config.root).unwrap();
+    }
+
+    #[test]
+    fn save_categories_replaces_only_those_categories() {
+        let config = Config::for_test("sqlite-save");
+        let store = SqliteStore::open(&config).unwrap();
+        store.insert(&Part::for_test("RES-0001", "resistor")).unwrap();
+        store.insert(&Part::for_test("CAP-0001", "capacitor")).unwrap();
+
+        let parts = [Part::for_test("RES-0002", "resistor"), Part::for_test("CAP-0002", "capacitor")];
+        store.save_categories(&parts, &["resistor"]).unwrap();
+        assert_eq!(ids(&store.load().unwrap()), ["CAP-0001", "RES-0002"]);
+        fs::remove_dir_all(&config.root).unwrap();
+    }
+
+    #[test]
+    fn refuses_newer_schemas() {
+        let config = Config::for_test("sqlite-schema");
+        drop(SqliteStore::open(&config).unwrap());
+
+        let conn = Connection::open(&config.storage.sqlite_path).unwrap();
+        conn.execute_batch("PRAGMA user_version = 99").unwrap();
+        assert!(SqliteStore::open(&config).is_err());
+        fs::remove_dir_all(&config.root).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+//! Part storage backends.
+//!
+//! Everything that reads or writes parts goes through [`PartStore`], so the
+//! rest of partman doesn't care whether a library keeps them in CSV files
+//! or a SQLite database. The backend is chosen by `[storage] backend`.
+
+use crate::{
+    config::{Config, StorageBackend},
+    part::Part,
+    Error, Result,
+};
+
+use super::{CsvStore, SqliteStore};
+
+pub trait PartStore {
+    /// Every part in every category
+    fn load(&self) -> Result<Vec<Part>>;
+
+    /// The part with this ID, if there is one
+    fn get(&self, id: &str) -> Result<Option<Part>> {
+        Ok(self.load()?.into_iter().find(|p| p.id.as_deref() == Some(id)))
+    }
+
+    /// Save a new part, its ID must already be assigned
+    fn insert(&self, part: &Part) -> Result<()>;
+
+    /// Replace the part with the same ID, which may move it to another
+    /// category
+    fn update(&self, part: &Part) -> Result<()> {
+        let mut parts = self.load()?;
+        let existing = parts.iter_mut()
+            .find(|p| p.id.is_some() && p.id == part.id)
+            .ok_or_else(|| no_part(part.id.as_deref().unwrap_or_default()))?;
+
+        let old_category = std::mem::replace(existing, part.clone()).category;
+        self.save_categories(&parts, &[&old_category, &part.category])
+    }
+
+    /// Delete a part, returning it
+    fn delete(&self, id: &str) -> Result<Part> {
+        let mut parts = self.load()?;
+        let index = parts.iter()
+            .position(|p| p.id.as_deref() == Some(id))
+            .ok_or_else(|| no_part(id))?;
+
+        let part = parts.remove(index);
+        self.save_categories(&parts, &[&part.category])?;
+        Ok(part)
+    }
+
+    /// The next free ID for a category code, e.g. `RES-0013`
+    fn next_id(&self, category_code: &str) -> Result<String> {
+        let parts = self.load()?;
+        Ok(next_id_in(parts.iter().filter_map(|p| p.id.as_deref()), category_code))
+    }
+
+    /// Replace the stored parts of every category in `categories` with the
+    /// parts in `parts` from those categories
+    fn save_categories(&self, parts: &[Part], categories: &[&str]) -> Result<()>;
+}
+
+/// Open the store selected by `[storage] backend`
+pub fn open_store(config: &Config) -> Result<Box<dyn PartStore + '_>> {
+    open_backend(config, config.storage.backend)
+}
+
+/// Open a store for a backend, whichever one the library is set to use
+pub fn open_backend(config: &Config, backend: StorageBackend) -> Result<Box<dyn PartStore + '_>> {
+    Ok(match backend {
+        StorageBackend::Csv => Box::new(CsvStore::new(config)),
+        StorageBackend::Sqlite => Box::new(SqliteStore::open(config)?),
+    })
+}
+
+pub(super) fn no_part(id: &str) -> Error {
+    Error::Validation(format!("No part with ID '{id}'"))
+}
+
+/// One more than the highest number used with the category code
+pub(super) fn next_id_in<'a>(ids: impl IntoIterator<Item = &'a str>, category_code: &str) -> String {
+    let max = ids
+        .into_iter()
+        .filter_map(|id| {
+            let mut splits = id.split('-');
+            let cat = splits.next()?;
+            if cat == category_code {
+                splits.next()?.parse::<u32>().ok()
+            } else {
+                None
+            }
+        })
+        .max()
+        .unwrap_or(0);
+    format!("{category_code}-{:04}", max + 1)
+}
@@ -0,0 +1,134 @@
+//! Local archive of part datasheets.
+//!
+//! Datasheets are downloaded into the configured directory as `{ID}.pdf`,
+//! and the file name, SHA-256 and source URL are recorded on the part so the
+//! archive can be checked later without downloading again.
+
+use std::{fs, path::Path};
+
+use serde::Serialize;
+
+use crate::{api::check_status, config::Config, part::Part, util::sha256_hex, Result};
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum Outcome {
+    Downloaded { file: String, sha256: String, bytes: usize },
+    /// Local file exists, matches the recorded hash and came from the
+    /// part's current datasheet URL
+    UpToDate { file: String },
+    /// Part has no datasheet URL
+    NoUrl,
+    Error { message: String },
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncResult {
+    pub id: String,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+/// File name a part's datasheet is stored under
+fn file_name(id: &str) -> String {
+    format!("{id}.pdf")
+}
+
+/// Whether the local copy exists, matches the recorded hash and was
+/// downloaded from `url`
+fn is_up_to_date(dir: &Path, part: &Part, url: &str) -> bool {
+    let (Some(file), Some(sha256)) = (&part.datasheet_file, &part.datasheet_sha256) else {
+        return false;
+    };
+    part.datasheet_source.as_deref() == Some(url)
+        && fs::read(dir.join(file)).is_ok_and(|bytes| &sha256_hex(&bytes) == sha256)
+}
+
+/// Whether the bytes are a PDF, which has its `%PDF-` header within the
+/// first 1024 bytes
+fn is_pdf(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(1024)].windows(5).any(|w| w == b"%PDF-")
+}
+
+fn download(http: &reqwest::blocking::Client, url: &str) -> Result<Vec<u8>> {
+    let response = check_status(http.get(url).send()?)?;
+    let content_type = response.headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown")
+        .to_owned();
+    let bytes = response.bytes()?.to_vec();
+    // Vendor sites often answer moved datasheets with an HTML page
+    if !is_pdf(&bytes) {
+        return Err(format!("{url} did not return a PDF (content type {content_type})").into());
+    }
+    Ok(bytes)
+}
+
+/// Download a part's datasheet into the archive, updating the part's file
+/// and hash columns. Parts already archived are skipped unless `force`.
+pub fn sync_part(http: &reqwest::blocking::Client, config: &Config, part: &mut Part, force: bool) -> SyncResult {
+    let id = part.id.clone().unwrap_or_default();
+    let dir = &config.datasheets.dir_path;
+
+    let Some(url) = part.datasheet.clone() else {
+        return SyncResult { id, outcome: Outcome::NoUrl };
+    };
+    if !force && is_up_to_date(dir, part, &url) {
+        let file = part.datasheet_file.clone().unwrap_or_default();
+        return SyncResult { id, outcome: Outcome::UpToDate { file } };
+    }
+
+    let result = download(http, &url).and_then(|bytes| {
+        fs::create_dir_all(dir)?;
+        let file = file_name(&id);
+        fs::write(dir.join(&file), &bytes)?;
+        Ok((file, sha256_hex(&bytes), bytes.len()))
+    });
+
+    let outcome = match result {
+        Ok((file, sha256, bytes)) => {
+            part.datasheet_file = Some(file.clone());
+            part.datasheet_sha256 = Some(sha256.clone());
+            part.datasheet_source = Some(url);
+            Outcome::Downloaded { file, sha256, bytes }
+        },
+        Err(e) => Outcome::Error { message: e.to_string() },
+    };
+    SyncResult { id, outcome }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_dir;
+
+    const URL: &str = "https://example.com/rc0402.pdf";
+
+    #[test]
+    fn finds_the_pdf_header() {
+        assert!(is_pdf(b"%PDF-1.7\n"));
+        assert!(is_pdf(b"\xef\xbb\xbf\r\n%PDF-1.4"));
+        assert!(!is_pdf(b"<!DOCTYPE html><html>Moved</html>"));
+        assert!(!is_pdf(b""));
+        assert!(!is_pdf(&[b" ".repeat(1024), b"%PDF-".to_vec()].concat()));
+    }
+
+    #[test]
+    fn up_to_date_needs_matching_file_hash_and_source() {
+        let dir = test_dir("datasheet");
+        let mut part = Part::for_test("RES-0001", "resistor");
+        assert!(!is_up_to_date(&dir, &part, URL));
+
+        fs::write(dir.join(file_name("RES-0001")), b"%PDF-1.7").unwrap();
+        part.datasheet_file = Some(file_name("RES-0001"));
+        part.datasheet_sha256 = Some(sha256_hex(b"%PDF-1.7"));
+        part.datasheet_source = Some(URL.into());
+        assert!(is_up_to_date(&dir, &part, URL));
+        assert!(!is_up_to_date(&dir, &part, "https://example.com/moved.pdf"));
+
+        fs::write(dir.join(file_name("RES-0001")), b"%PDF-1.7 edited").unwrap();
+        assert!(!is_up_to_date(&dir, &part, URL));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
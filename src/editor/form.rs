@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
 use crate::config::Cat;
-use crate::api;
+use crate::{api, Error, Result};
 
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -101,41 +101,131 @@ impl Form {
     // TODO: Move statuses to config
     pub(super) const STATUSES: &str = "[new, verified, issues, obsolete]";
 
-}
-
-
-impl From<api::digikey::DigikeyProduct> for Form {
-    fn from(value: api::digikey::DigikeyProduct) -> Self {
+    /// A form with every field empty, used as the base for non-interactive adds
+    pub fn blank() -> Self {
         Self {
             about: About {
-                // TODO: Pull out more info for category and subcategory
-                category: value.category.name,
-                description: value.description.product_description,
-                ..Default::default()
+                id: None,
+                category: String::new(),
+                subcategory: String::new(),
+                description: String::new(),
+                value: String::new(),
             },
             component: Component {
-                manufacturer: value.manufacturer.name,
-                mpn: value.manufacturer_product_number,
-                // package: value,
-                datasheet: value.datasheet_url.unwrap_or("".to_string()),
-                ..Default::default()
+                manufacturer: String::new(),
+                mpn: String::new(),
+                package: String::new(),
+                datasheet: String::new(),
             },
             suppliers: Suppliers {
-                // supplier1: value,
-                spn1: value.search_spn.unwrap_or("".to_string()),
-                // supplier2: value,
-                // spn2: value,
-                ..Default::default()
+                supplier1: String::new(),
+                spn1: String::new(),
+                supplier2: String::new(),
+                spn2: String::new(),
             },
             library: Library {
-                // symbol: value,
-                // footprint: value,
-                // status: value,
-                // notes: value,
-                ..Default::default()
+                symbol: String::new(),
+                footprint: String::new(),
+                status: String::from("New"),
+                notes: String::new(),
             },
-            long_description: Some(value.description.detailed_description),
-            ..Default::default()
+            categories: None,
+            long_description: None,
+        }
+    }
+
+    /// Parse a complete form from TOML, or JSON if the content is a JSON object
+    pub fn parse(content: &str) -> Result<Self> {
+        if content.trim_start().starts_with('{') {
+            Ok(serde_json::from_str(content)?)
+        } else {
+            Ok(toml::from_str(content)?)
         }
     }
-}
\ No newline at end of file
+
+    /// Set a single field by name, either bare (`value`) or qualified by
+    /// its section (`about.value`)
+    pub fn set_field(&mut self, key: &str, value: &str) -> Result<()> {
+        let name = key.rsplit('.').next().unwrap_or(key);
+        let field = match name {
+            "category" => &mut self.about.category,
+            "subcategory" => &mut self.about.subcategory,
+            "description" => &mut self.about.description,
+            "value" => &mut self.about.value,
+            "manufacturer" => &mut self.component.manufacturer,
+            "mpn" => &mut self.component.mpn,
+            "package" => &mut self.component.package,
+            "datasheet" => &mut self.component.datasheet,
+            "supplier1" => &mut self.suppliers.supplier1,
+            "spn1" => &mut self.suppliers.spn1,
+            "supplier2" => &mut self.suppliers.supplier2,
+            "spn2" => &mut self.suppliers.spn2,
+            "symbol" => &mut self.library.symbol,
+            "footprint" => &mut self.library.footprint,
+            "status" => &mut self.library.status,
+            "notes" => &mut self.library.notes,
+            _ => return Err(Error::Validation(format!("Unknown form field '{key}'"))),
+        };
+        *field = value.to_owned();
+        Ok(())
+    }
+}
+
+
+impl From<api::digikey::DigikeyProduct> for Form {
+    fn from(value: api::digikey::DigikeyProduct) -> Self {
+        Form::default().with_digikey(value)
+    }
+}
+
+impl Form {
+    /// Fill the fields a DigiKey product covers, keeping the rest of `self`
+    /// so non-interactive adds can start from [`Form::blank`] instead of the
+    /// sample part
+    pub fn with_digikey(mut self, value: api::digikey::DigikeyProduct) -> Self {
+        // TODO: Pull out more info for category and subcategory
+        self.about.category = value.category.name;
+        self.about.description = value.description.product_description;
+        self.component.manufacturer = value.manufacturer.name;
+        self.component.mpn = value.manufacturer_product_number;
+        self.component.datasheet = value.datasheet_url.unwrap_or_default();
+        self.suppliers.supplier1 = String::from("Digi-Key");
+        self.suppliers.spn1 = value.search_spn.unwrap_or_default();
+        self.long_description = Some(value.description.detailed_description);
+        self
+    }
+}
+
+impl From<api::lcsc::LcscProduct> for Form {
+    fn from(value: api::lcsc::LcscProduct) -> Self {
+        let mut form = Form::blank();
+        form.about.category = value.category.unwrap_or_default();
+        form.about.description = value.description.clone().unwrap_or_default();
+        form.component.manufacturer = value.manufacturer.unwrap_or_default();
+        form.component.mpn = value.mpn;
+        form.component.package = value.package.unwrap_or_default();
+        form.component.datasheet = value.datasheet_url.unwrap_or_default();
+        form.suppliers.supplier2 = String::from("LCSC");
+        form.suppliers.spn2 = value.code;
+        form.long_description = value.description;
+        form
+    }
+}
+
+impl Form {
+    /// Fill the second supplier slot from an LCSC product, keeping any
+    /// component fields already filled by the primary supplier
+    pub fn apply_lcsc(&mut self, product: api::lcsc::LcscProduct) {
+        let fill = |field: &mut String, value: Option<String>| {
+            if field.trim().is_empty() {
+                *field = value.unwrap_or_default();
+            }
+        };
+        fill(&mut self.component.manufacturer, product.manufacturer);
+        fill(&mut self.component.mpn, Some(product.mpn));
+        fill(&mut self.component.package, product.package);
+        fill(&mut self.component.datasheet, product.datasheet_url);
+        self.suppliers.supplier2 = String::from("LCSC");
+        self.suppliers.spn2 = product.code;
+    }
+}
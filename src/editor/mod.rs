@@ -1,10 +1,9 @@
 use std::{
     fs,
-    path::{Path, PathBuf},
+    path::Path,
     process::Command,
-    time::{SystemTime, UNIX_EPOCH},
 };
-use crate::{Result, config::Config, data, part::Part};
+use crate::{Error, Library, Result, datasheet, part::Part};
 
 mod form;
 mod display;
@@ -21,42 +20,23 @@ fn clean(text: &str) -> Vec<&str> {
         .collect::<Vec<_>>()
 }
 
-pub fn edit(editor: &str, history_path: &Path, form: Form) -> Result<Option<Form>> {
-    // Create temporary file with timestamp
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs()
-        .to_string();
-    let mut temp_file: PathBuf = history_path.into();
-    temp_file.push(timestamp);
-    temp_file.set_extension("toml");
-
-    // Make sure the directories exist or else the write will fail
-    if let Some(parent_dir) = temp_file.parent() {
-        fs::create_dir_all(parent_dir)?;
-    }
-    fs::write(&temp_file, form.to_string())?;
-
-    edit_file(editor, &temp_file)
-}
-
 pub fn edit_file(editor: &str, file: &Path) -> Result<Option<Form>> {
      // Save original for comparison later
      let original_content = fs::read_to_string(file)?;
 
      // First element is editor command, rest are the arguments
     let mut editor_parts = editor.split_whitespace();
-    let editor_cmd = editor_parts.next().expect("Error: No editor provided in config");
+    let editor_cmd = editor_parts.next()
+        .ok_or_else(|| Error::Editor("No editor provided in config".into()))?;
     
     let status = Command::new(editor_cmd)
         .args(editor_parts)
         .arg(file)
-        .status()?;
+        .status()
+        .map_err(|e| Error::Editor(format!("Could not start '{editor_cmd}': {e}")))?;
 
     if !status.success() {
-        return Err(
-            format!("Editor returned a non-zero exit code::\n{status}").into())
+        return Err(Error::Editor(format!("Editor returned a non-zero exit code: {status}")));
     }
 
     let new_content = fs::read_to_string(file)?;
@@ -69,22 +49,22 @@ pub fn edit_file(editor: &str, file: &Path) -> Result<Option<Form>> {
     Ok(toml::from_str(&new_content)?)
 }
 
-pub fn process_form(edited_form: Form, config: &Config) -> Result<()> {
-    let mut new_part: Part = edited_form.into();
-    let mut parts = data::load_all_parts(&config.cat, &config.csv_dir_path)?;
-    let code = &config.cat
-        .get(&new_part.category)
-        .ok_or("Error: Part category incorrect")?
-        .code;
-    new_part.id = Some(data::get_next_id(&parts, code)?);
-
-    // TODO: Split different verifications out such as id etc.
-    new_part.validate(config)?;
-
-    data::insert_part(&mut new_part, config, &config.csv_dir_path)?;
-    parts.push(new_part);
-    data::build_kicad_db(&config.db_file_path, parts, &config.cat)?;    
+/// Validate and save an edited form as a new part, returning the saved part
+pub fn process_form(edited_form: Form, library: &Library, download_datasheet: bool) -> Result<Part> {
+    let mut new_part = library.add_part(edited_form.into())?;
+
+    // A failed download shouldn't lose the part, `datasheets sync` can retry
+    if download_datasheet {
+        let result = datasheet::sync_part(&reqwest::blocking::Client::new(), library.config(), &mut new_part, false);
+        match result.outcome {
+            datasheet::Outcome::Downloaded { .. } => library.update_part(new_part.clone())?,
+            datasheet::Outcome::Error { message } => eprintln!("Warning: could not download datasheet: {message}"),
+            _ => {},
+        }
+    }
+    let report = library.build()?;
+    eprintln!("Successfully built KiCad DB: {}", report.db_path.display());
 
-    println!("Successfully added new part");
-    Ok(())
+    eprintln!("Successfully added new part {}", new_part.id.as_deref().unwrap_or_default());
+    Ok(new_part)
 }
\ No newline at end of file
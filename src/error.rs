@@ -0,0 +1,264 @@
+//! Error type shared by every partman command.
+//!
+//! Each variant maps to a distinct process exit code and a stable machine
+//! readable code for `--output json`, and most carry a hint telling the
+//! user how to fix the problem.
+
+use std::{fmt, io, path::{Path, PathBuf}};
+
+#[derive(Debug)]
+pub enum Error {
+    /// No partman.toml in `dir`, or in any parent of it when the library
+    /// was searched for from the current directory
+    ConfigNotFound { dir: PathBuf, searched_parents: bool },
+
+    /// A config file could not be parsed
+    ConfigParse {
+        path: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+
+    /// The config parsed but failed validation
+    ConfigInvalid(Vec<String>),
+
+    /// Credentials for a supplier API are missing
+    SecretsMissing { service: &'static str },
+
+    /// A credential field exists but is empty
+    SecretsEmpty { service: &'static str, field: &'static str },
+
+    /// A CSV file could not be read or a row could not be parsed
+    Csv {
+        path: Option<PathBuf>,
+        line: Option<u64>,
+        column: Option<String>,
+        message: String,
+    },
+
+    /// SQLite error while reading or building a database
+    Db(rusqlite::Error),
+
+    /// Supplier API returned a non-success status
+    Api { status: u16, body: String },
+
+    /// Network or HTTP client error
+    Http(reqwest::Error),
+
+    /// The editor could not be started or exited with an error
+    Editor(String),
+
+    /// A part or form failed validation
+    Validation(String),
+
+    /// User supplied TOML or JSON could not be parsed
+    Parse(String),
+
+    /// A check command found problems, which have already been reported
+    CheckFailed { problems: usize },
+
+    Io(io::Error),
+
+    Other(String),
+}
+
+impl Error {
+    /// Build a config parse error, resolving the byte span to a line number
+    pub fn config_parse(path: &Path, content: &str, err: toml::de::Error) -> Self {
+        let line = err.span().map(|span| content[..span.start].matches('\n').count() + 1);
+        Self::ConfigParse {
+            path: path.to_path_buf(),
+            line,
+            message: err.message().to_owned(),
+        }
+    }
+
+    /// Build a CSV error for a file, naming the column when it is known
+    pub fn csv(path: &Path, headers: &[&str], err: csv::Error) -> Self {
+        let mut line = err.position().map(|pos| pos.line());
+        let mut column = None;
+        let mut message = err.to_string();
+        match err.kind() {
+            csv::ErrorKind::Deserialize { pos, err: de } => {
+                line = pos.as_ref().map(|pos| pos.line());
+                column = de.field()
+                    .and_then(|i| headers.get(i as usize))
+                    .map(|name| name.to_string());
+                message = de.kind().to_string();
+            },
+            csv::ErrorKind::UnequalLengths { pos, expected_len, len } => {
+                line = pos.as_ref().map(|pos| pos.line());
+                message = format!("expected {expected_len} fields, found {len}");
+            },
+            _ => {},
+        }
+        Self::Csv {
+            path: Some(path.to_path_buf()),
+            line,
+            column,
+            message,
+        }
+    }
+
+    /// Process exit code for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Other(_) | Error::Io(_) => 1,
+            Error::ConfigNotFound { .. } => 3,
+            Error::ConfigParse { .. } | Error::ConfigInvalid(_) => 4,
+            Error::SecretsMissing { .. } | Error::SecretsEmpty { .. } => 5,
+            Error::Csv { .. } => 6,
+            Error::Db(_) => 7,
+            Error::Api { .. } | Error::Http(_) => 8,
+            Error::Editor(_) => 9,
+            Error::Validation(_) | Error::Parse(_) => 10,
+            Error::CheckFailed { .. } => 11,
+        }
+    }
+
+    /// Stable machine readable code for this error
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ConfigNotFound { .. } => "config_not_found",
+            Error::ConfigParse { .. } => "config_parse",
+            Error::ConfigInvalid(_) => "config_invalid",
+            Error::SecretsMissing { .. } => "secrets_missing",
+            Error::SecretsEmpty { .. } => "secrets_empty",
+            Error::Csv { .. } => "csv",
+            Error::Db(_) => "database",
+            Error::Api { .. } => "api",
+            Error::Http(_) => "http",
+            Error::Editor(_) => "editor",
+            Error::Validation(_) => "validation",
+            Error::Parse(_) => "parse",
+            Error::CheckFailed { .. } => "check_failed",
+            Error::Io(_) => "io",
+            Error::Other(_) => "error",
+        }
+    }
+
+    /// Suggestion for how to fix the error, if there is one
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Error::ConfigNotFound { .. } => Some("run `partman init` to create a new library here".into()),
+            Error::ConfigParse { path, .. } => Some(format!("fix the syntax in {}", path.display())),
+            Error::ConfigInvalid(_) => Some("run `partman config check` for details".into()),
+            Error::SecretsMissing { service } => Some(format!(
+                "add a [{service}] section to secrets.toml or the user config"
+            )),
+            Error::SecretsEmpty { service, field } => Some(format!(
+                "fill {field} under [{service}] in secrets.toml, or set {}_{}",
+                service.to_uppercase(),
+                field.to_uppercase()
+            )),
+            Error::Csv { path: Some(path), .. } => Some(format!("fix the row in {}", path.display())),
+            Error::Api { status: 401, .. } => Some("check the API credentials in secrets.toml".into()),
+            Error::Api { status: 404, .. } => Some("check the supplier part number".into()),
+            Error::Editor(_) => Some("check editor_cmd in partman.toml".into()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ConfigNotFound { dir, searched_parents: true } => write!(f, "Could not find partman.toml in {} or any parent directory", dir.display()),
+            Error::ConfigNotFound { dir, searched_parents: false } => write!(f, "Could not find partman.toml in {}", dir.display()),
+            Error::ConfigParse { path, line: Some(line), message } => write!(f, "Failed to parse {}:{}: {}", path.display(), line, message),
+            Error::ConfigParse { path, line: None, message } => write!(f, "Failed to parse {}: {}", path.display(), message),
+            Error::ConfigInvalid(issues) => {
+                write!(f, "Invalid partman.toml:")?;
+                for issue in issues {
+                    write!(f, "\n  {issue}")?;
+                }
+                Ok(())
+            },
+            Error::SecretsMissing { service } => write!(f, "{service} credentials missing"),
+            Error::SecretsEmpty { service, field } => write!(f, "{service} {field} is empty"),
+            Error::Csv { path, line, column, message } => {
+                write!(f, "CSV error")?;
+                if let Some(path) = path {
+                    write!(f, " in {}", path.display())?;
+                }
+                if let Some(line) = line {
+                    write!(f, " on line {line}")?;
+                }
+                if let Some(column) = column {
+                    write!(f, " column '{column}'")?;
+                }
+                write!(f, ": {message}")
+            },
+            Error::Db(e) => write!(f, "Database error: {e}"),
+            Error::Api { status, body } => write!(f, "API request failed with HTTP {status}: {body}"),
+            Error::Http(e) => write!(f, "HTTP error: {e}"),
+            Error::Editor(msg) => write!(f, "Editor error: {msg}"),
+            Error::Validation(msg) => write!(f, "Validation error: {msg}"),
+            Error::Parse(msg) => write!(f, "Parse error: {msg}"),
+            Error::CheckFailed { problems } => write!(f, "Check failed with {problems} problem(s)"),
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Other(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        Error::Csv {
+            path: None,
+            line: e.position().map(|pos| pos.line()),
+            column: None,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Db(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<reqwest::header::InvalidHeaderValue> for Error {
+    fn from(e: reqwest::header::InvalidHeaderValue) -> Self {
+        Error::Other(format!("Invalid HTTP header value: {e}"))
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<String> for Error {
+    fn from(msg: String) -> Self {
+        Error::Other(msg)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(msg: &str) -> Self {
+        Error::Other(msg.to_owned())
+    }
+}
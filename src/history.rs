@@ -0,0 +1,154 @@
+//! Draft history management.
+//!
+//! Every add or resume works on a draft file in the history directory named
+//! `<unix_seconds>.toml` (with a `-N` suffix when several drafts are created
+//! in the same second). Once a draft has been saved as a part, a marker
+//! comment recording the part ID is appended to the file, replacing the
+//! marker of any earlier save.
+
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{editor::Form, util::unix_now, Error, Result};
+
+const COMMITTED_MARKER: &str = "# partman: committed as ";
+
+/// A single draft file in the history directory
+#[derive(Debug)]
+pub struct Draft {
+    pub id: String,
+    pub path: PathBuf,
+    pub timestamp: u64,
+    pub committed: Option<String>,
+}
+
+impl Draft {
+    fn from_path(path: PathBuf) -> Option<Self> {
+        if path.extension() != Some(OsStr::new("toml")) {
+            return None;
+        }
+        let id = path.file_stem()?.to_str()?.to_owned();
+        let timestamp = id.split('-').next()?.parse::<u64>().ok()?;
+        let committed = fs::read_to_string(&path)
+            .ok()?
+            .lines()
+            .find_map(|line| line.strip_prefix(COMMITTED_MARKER))
+            .map(|s| s.trim().to_owned());
+
+        Some(Self { id, path, timestamp, committed })
+    }
+
+    /// Parse the draft contents, `None` if the file is not a valid form
+    pub fn form(&self) -> Option<Form> {
+        let content = fs::read_to_string(&self.path).ok()?;
+        toml::from_str(&content).ok()
+    }
+}
+
+/// Write a form to a new uniquely named draft file
+pub fn create(history_dir: &Path, form: &Form) -> Result<Draft> {
+    // Make sure the directories exist or else the write will fail
+    fs::create_dir_all(history_dir)?;
+
+    let timestamp = unix_now();
+    let mut id = timestamp.to_string();
+    let mut suffix = 0;
+    while history_dir.join(&id).with_extension("toml").exists() {
+        suffix += 1;
+        id = format!("{timestamp}-{suffix}");
+    }
+
+    let path = history_dir.join(&id).with_extension("toml");
+    fs::write(&path, form.to_string())?;
+
+    Ok(Draft { id, path, timestamp, committed: None })
+}
+
+/// All drafts in the history directory, oldest first
+pub fn list(history_dir: &Path) -> Result<Vec<Draft>> {
+    if !history_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut drafts: Vec<Draft> = fs::read_dir(history_dir)?
+        .flatten()
+        .filter_map(|ent| Draft::from_path(ent.path()))
+        .collect();
+
+    // Suffixes sort numerically within the same second
+    drafts.sort_by_key(|d| {
+        let suffix = d.id.split_once('-')
+            .and_then(|(_, s)| s.parse::<u32>().ok())
+            .unwrap_or(0);
+        (d.timestamp, suffix)
+    });
+
+    Ok(drafts)
+}
+
+/// Find a draft by ID, or the newest one if no ID is given
+pub fn find(history_dir: &Path, id: Option<&str>) -> Result<Draft> {
+    let drafts = list(history_dir)?;
+    match id {
+        None => drafts.into_iter().last()
+            .ok_or_else(|| Error::Validation("Could not find a draft to resume from".into())),
+        Some(id) => drafts.into_iter().find(|d| d.id == id)
+            .ok_or_else(|| Error::Validation(format!("No draft with ID '{id}' in history"))),
+    }
+}
+
+/// Record that a draft has been saved as the part with the given ID,
+/// replacing the marker of any earlier save
+pub fn mark_committed(draft: &Path, part_id: &str) -> Result<()> {
+    let mut content: String = fs::read_to_string(draft)?
+        .lines()
+        .filter(|line| !line.starts_with(COMMITTED_MARKER))
+        .flat_map(|line| [line, "\n"])
+        .collect();
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&format!("{COMMITTED_MARKER}{part_id}\n"));
+    fs::write(draft, content)?;
+    Ok(())
+}
+
+/// Delete drafts older than `age`, returning the deleted drafts
+pub fn prune(history_dir: &Path, age: Duration, committed_only: bool) -> Result<Vec<Draft>> {
+    let cutoff = unix_now().saturating_sub(age.as_secs());
+    let mut pruned = Vec::new();
+
+    for draft in list(history_dir)? {
+        if draft.timestamp >= cutoff || (committed_only && draft.committed.is_none()) {
+            continue;
+        }
+        fs::remove_file(&draft.path)?;
+        pruned.push(draft);
+    }
+
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_dir;
+
+    #[test]
+    fn commit_marker_is_replaced() {
+        let dir = test_dir("history");
+        let draft = create(&dir, &Form::blank()).unwrap();
+        mark_committed(&draft.path, "RES-0001").unwrap();
+        mark_committed(&draft.path, "RES-0002").unwrap();
+
+        let content = fs::read_to_string(&draft.path).unwrap();
+        assert_eq!(content.matches(COMMITTED_MARKER).count(), 1);
+        assert_eq!(find(&dir, None).unwrap().committed.as_deref(), Some("RES-0002"));
+        assert!(find(&dir, Some("0")).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,110 @@
+//! Offline import of the JLCPCB assembly parts library.
+//!
+//! JLCPCB publishes its parts library as a CSV download with one row per
+//! LCSC part and a "Library Type" column saying whether the part is a basic,
+//! preferred or extended part. Basic parts have no feeder loading fee.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use csv::ReaderBuilder;
+
+use crate::{Error, Result};
+
+/// Header names used for the LCSC part number in different exports, most
+/// specific first
+const CODE_HEADERS: &[&str] = &["lcsc part", "lcsc part #", "lcsc", "lcsc_part", "jlcpcb part #"];
+
+/// Header names used for the library type in different exports, most
+/// specific first so a generic "Type" column is only used as a fallback
+const TYPE_HEADERS: &[&str] = &["library type", "library_type", "type", "basic/extended"];
+
+/// The column of the first name in `names` found in the headers
+fn find_column(headers: &csv::StringRecord, names: &[&str]) -> Option<usize> {
+    names.iter().find_map(|name| {
+        headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name))
+    })
+}
+
+/// Read library types for the wanted LCSC part numbers from a JLCPCB parts
+/// library CSV, returning a map of part number to lowercase type
+pub fn load_types(path: &Path, wanted: &HashSet<String>) -> Result<HashMap<String, String>> {
+    let mut rdr = ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| Error::csv(path, &[], e))?;
+    let headers = rdr.headers().map_err(|e| Error::csv(path, &[], e))?.clone();
+
+    let code_col = find_column(&headers, CODE_HEADERS).ok_or_else(|| Error::Csv {
+        path: Some(path.to_path_buf()),
+        line: Some(1),
+        column: None,
+        message: "no 'LCSC Part' column found".into(),
+    })?;
+    let type_col = find_column(&headers, TYPE_HEADERS).ok_or_else(|| Error::Csv {
+        path: Some(path.to_path_buf()),
+        line: Some(1),
+        column: None,
+        message: "no 'Library Type' column found".into(),
+    })?;
+
+    let mut types = HashMap::new();
+    for record in rdr.records() {
+        let record = record.map_err(|e| Error::csv(path, &[], e))?;
+        let (Some(code), Some(kind)) = (record.get(code_col), record.get(type_col)) else {
+            continue;
+        };
+        let code = code.trim().to_uppercase();
+        if wanted.contains(&code) {
+            types.insert(code, kind.trim().to_lowercase());
+        }
+    }
+
+    Ok(types)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::util::test_dir;
+
+    fn load(name: &str, content: &str, wanted: &[&str]) -> Result<HashMap<String, String>> {
+        let dir = test_dir(name);
+        let path = dir.join("jlc.csv");
+        fs::write(&path, content).unwrap();
+        let wanted = wanted.iter().map(|s| s.to_string()).collect();
+        let types = load_types(&path, &wanted);
+        fs::remove_dir_all(dir).unwrap();
+        types
+    }
+
+    #[test]
+    fn reads_wanted_types() {
+        let types = load(
+            "types",
+            "LCSC Part,First Category,MFR.Part,Library Type\nc1525,Capacitors,CL05B104KO5NNNC,Basic\nC2,x,y,Extended\n",
+            &["C1525", "C9"],
+        ).unwrap();
+        assert_eq!(types.len(), 1);
+        assert_eq!(types["C1525"], "basic");
+    }
+
+    #[test]
+    fn prefers_library_type_over_type() {
+        let types = load(
+            "priority",
+            "Type,LCSC Part #,Library Type\nCapacitor,C1525,Extended\n",
+            &["C1525"],
+        ).unwrap();
+        assert_eq!(types["C1525"], "extended");
+    }
+
+    #[test]
+    fn requires_columns() {
+        assert!(load("missing", "LCSC Part,Category\nC1525,Capacitors\n", &["C1525"]).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! Kitting a build: checking a design's parts are on hand for a number of
+//! boards and taking them out of stock.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{
+    bom::BomLine,
+    data::{LedgerEntry, StockEntry},
+};
+
+/// Stock taken from one location
+#[derive(Debug, Serialize)]
+pub struct Take {
+    pub location: String,
+    pub qty: u32,
+}
+
+/// A library part needed for the build
+#[derive(Debug, Serialize)]
+pub struct KitLine {
+    pub id: String,
+    pub description: Option<String>,
+    pub needed: u32,
+    pub on_hand: u32,
+    /// Quantity missing from stock, zero if the line can be kitted
+    pub short: u32,
+    pub takes: Vec<Take>,
+    pub references: Vec<String>,
+}
+
+/// Components that aren't in the library, so have no stock to take
+#[derive(Debug, Serialize)]
+pub struct Unmatched {
+    pub lib_id: String,
+    pub value: String,
+    pub needed: u32,
+    pub references: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Kit {
+    pub project: String,
+    pub boards: u32,
+    pub lines: Vec<KitLine>,
+    pub unmatched: Vec<Unmatched>,
+}
+
+impl Kit {
+    /// Lines short of stock plus components not in the library
+    pub fn problems(&self) -> usize {
+        self.lines.iter().filter(|l| l.short > 0).count() + self.unmatched.len()
+    }
+}
+
+/// Work out what to take from stock for `boards` boards. Each part is taken
+/// from its smallest holdings first so part-used reels and bags run out
+/// before full ones are opened.
+pub fn plan(lines: &[BomLine], stock: &[StockEntry], project: &str, boards: u32) -> Kit {
+    let mut held: BTreeMap<&str, Vec<&StockEntry>> = BTreeMap::new();
+    for entry in stock.iter().filter(|e| e.qty > 0) {
+        held.entry(&entry.id).or_default().push(entry);
+    }
+
+    let mut kit = Kit {
+        project: project.to_owned(),
+        boards,
+        lines: Vec::new(),
+        unmatched: Vec::new(),
+    };
+    for line in lines {
+        let needed = u32::try_from(line.qty).unwrap_or(u32::MAX).saturating_mul(boards);
+        let Some(id) = &line.id else {
+            kit.unmatched.push(Unmatched {
+                lib_id: line.lib_id.clone(),
+                value: line.value.clone(),
+                needed,
+                references: line.references.clone(),
+            });
+            continue;
+        };
+
+        let mut locations = held.get(id.as_str()).cloned().unwrap_or_default();
+        locations.sort_by_key(|e| (e.qty, e.location.as_str()));
+        let on_hand = locations.iter().map(|e| e.qty).sum();
+
+        let mut takes = Vec::new();
+        let mut remaining = needed;
+        for entry in locations {
+            if remaining == 0 {
+                break;
+            }
+            let qty = remaining.min(entry.qty);
+            takes.push(Take { location: entry.location.clone(), qty });
+            remaining -= qty;
+        }
+
+        kit.lines.push(KitLine {
+            id: id.clone(),
+            description: line.description.clone(),
+            needed,
+            on_hand,
+            short: remaining,
+            takes,
+            references: line.references.clone(),
+        });
+    }
+    kit
+}
+
+/// Take the kit out of stock, returning the ledger entries for the change
+pub fn apply(kit: &Kit, stock: &mut [StockEntry], time: &str) -> Vec<LedgerEntry> {
+    let mut ledger = Vec::new();
+    for line in &kit.lines {
+        for take in &line.takes {
+            let Some(entry) = stock.iter_mut().find(|e| e.id == line.id && e.location == take.location) else {
+                continue;
+            };
+            entry.qty = entry.qty.saturating_sub(take.qty);
+            ledger.push(LedgerEntry {
+                time: time.to_owned(),
+                id: line.id.clone(),
+                location: take.location.clone(),
+                change: -i64::from(take.qty),
+                action: "kit".into(),
+                project: kit.project.clone(),
+            });
+        }
+    }
+    ledger
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bom::{self, Component}, part::Part};
+
+    fn stock(id: &str, location: &str, qty: u32) -> StockEntry {
+        StockEntry { id: id.into(), location: location.into(), qty, lot: String::new() }
+    }
+
+    fn lines() -> Vec<BomLine> {
+        let component = |reference: &str, lib_id: &str| Component {
+            reference: reference.into(),
+            lib_id: lib_id.into(),
+            value: "100n".into(),
+            fields: Default::default(),
+        };
+        let components = [
+            component("C1", "Partman:CAP-0001"),
+            component("C2", "Partman:CAP-0001"),
+            component("C3", "Partman:CAP-0002"),
+            component("U1", "MCU:STM32"),
+        ];
+        let parts = [Part::for_test("CAP-0001", "capacitor"), Part::for_test("CAP-0002", "capacitor")];
+        bom::build(&components, &parts)
+    }
+
+    #[test]
+    fn takes_smallest_holdings_first() {
+        let stock = [
+            stock("CAP-0001", "reel", 4000),
+            stock("CAP-0001", "bag-b", 3),
+            stock("CAP-0001", "empty", 0),
+            stock("CAP-0001", "bag-a", 3),
+            stock("CAP-0002", "bin", 10),
+        ];
+        let kit = plan(&lines(), &stock, "board", 5);
+
+        let line = &kit.lines[0];
+        assert_eq!((line.needed, line.on_hand, line.short), (10, 4006, 0));
+        let takes: Vec<(&str, u32)> = line.takes.iter().map(|t| (t.location.as_str(), t.qty)).collect();
+        assert_eq!(takes, [("bag-a", 3), ("bag-b", 3), ("reel", 4)]);
+
+        assert_eq!(kit.lines[1].short, 0);
+        assert_eq!(kit.unmatched[0].lib_id, "MCU:STM32");
+        assert_eq!(kit.problems(), 1);
+    }
+
+    #[test]
+    fn reports_shortages() {
+        let stock = [stock("CAP-0001", "bag", 3)];
+        let kit = plan(&lines(), &stock, "board", 2);
+        let short: Vec<(&str, u32)> = kit.lines.iter().map(|l| (l.id.as_str(), l.short)).collect();
+        assert_eq!(short, [("CAP-0001", 1), ("CAP-0002", 2)]);
+        assert_eq!(kit.problems(), 3);
+    }
+
+    #[test]
+    fn apply_takes_stock_and_logs_it() {
+        let mut stock = vec![stock("CAP-0001", "reel", 100), stock("CAP-0001", "bag", 1), stock("CAP-0002", "bin", 5)];
+        let kit = plan(&lines(), &stock, "board", 1);
+        let ledger = apply(&kit, &mut stock, "2026-01-01 00:00");
+
+        let left: Vec<u32> = stock.iter().map(|e| e.qty).collect();
+        assert_eq!(left, [99, 0, 4]);
+        let changes: Vec<(&str, &str, i64)> = ledger.iter()
+            .map(|e| (e.id.as_str(), e.location.as_str(), e.change))
+            .collect();
+        assert_eq!(changes, [("CAP-0001", "bag", -1), ("CAP-0001", "reel", -1), ("CAP-0002", "bin", -1)]);
+        assert!(ledger.iter().all(|e| e.action == "kit" && e.project == "board"));
+    }
+}
@@ -0,0 +1,58 @@
+//! Partman, a part library manager for KiCad database libraries.
+//!
+//! Parts are kept in one CSV file per category, or a SQLite database, and
+//! built into the SQLite file KiCad reads. [`Library`] is the entry point
+//! for tools built on top of partman: it owns the loaded config and covers
+//! reading and writing parts, building the DB, validation and supplier
+//! lookups.
+//!
+//! The stable API is what's re-exported here along with the [`api`],
+//! [`config`] and [`part`] modules. The hidden modules are the pieces the
+//! `partman` CLI is built from, they are public only so the binary can use
+//! them and may change in any release.
+
+pub mod api;
+pub mod config;
+pub mod part;
+mod error;
+mod library;
+
+#[doc(hidden)]
+pub mod barcode;
+#[doc(hidden)]
+pub mod bom;
+#[doc(hidden)]
+pub mod cost;
+#[doc(hidden)]
+pub mod crossref;
+#[doc(hidden)]
+pub mod data;
+#[doc(hidden)]
+pub mod datasheet;
+#[doc(hidden)]
+pub mod editor;
+#[doc(hidden)]
+pub mod history;
+#[doc(hidden)]
+pub mod jlc;
+#[doc(hidden)]
+pub mod kit;
+#[doc(hidden)]
+pub mod order;
+#[doc(hidden)]
+pub mod refresh;
+#[doc(hidden)]
+pub mod reorder;
+#[doc(hidden)]
+pub mod report;
+#[doc(hidden)]
+pub mod util;
+
+pub use api::{digikey, lcsc};
+pub use config::Config;
+pub use data::{BuildReport, PartStore};
+pub use error::Error;
+pub use library::Library;
+pub use part::Part;
+
+pub type Result<T> = core::result::Result<T, Error>;
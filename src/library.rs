@@ -0,0 +1,124 @@
+//! A part library: its loaded config and the operations tools need on it.
+
+use std::path::Path;
+
+use crate::{
+    api::{digikey, lcsc},
+    config::{Config, Issue},
+    data::{self, BuildReport, PartStore},
+    Error, Part, Result,
+};
+
+/// A part library opened from its partman.toml
+///
+/// Writes go straight to the part store. The KiCad DB is only
+/// rebuilt by [`Library::build`], so several changes can be made before
+/// building once.
+#[derive(Debug)]
+pub struct Library {
+    config: Config,
+}
+
+impl Library {
+    /// Open the library found in the current directory or a parent,
+    /// failing if its config has errors
+    pub fn open() -> Result<Self> {
+        Ok(Self { config: Config::find_and_load()? })
+    }
+
+    /// Open the library found in the current directory or a parent without
+    /// validating its config
+    pub fn open_unchecked() -> Result<Self> {
+        Ok(Self { config: Config::find_and_load_unchecked()? })
+    }
+
+    /// Open the library whose partman.toml is in `root`, failing if its
+    /// config has errors
+    pub fn open_at(root: &Path) -> Result<Self> {
+        Ok(Self { config: Config::load(root)? })
+    }
+
+    /// Use an already loaded config, paths in it are used as they are
+    pub fn from_config(config: Config) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Problems with the config, errors and warnings
+    pub fn validate(&self) -> Vec<Issue> {
+        self.config.validate()
+    }
+
+    /// The store the library's parts are kept in, see `[storage] backend`
+    pub fn store(&self) -> Result<Box<dyn PartStore + '_>> {
+        data::open_store(&self.config)
+    }
+
+    /// Every part in every category
+    pub fn parts(&self) -> Result<Vec<Part>> {
+        self.store()?.load()
+    }
+
+    /// The part with this ID
+    pub fn part(&self, id: &str) -> Result<Part> {
+        self.store()?
+            .get(id)?
+            .ok_or_else(|| Error::Validation(format!("No part with ID '{id}'")))
+    }
+
+    /// Assign the next ID in the part's category, validate it and save it,
+    /// returning the saved part
+    pub fn add_part(&self, mut part: Part) -> Result<Part> {
+        let store = self.store()?;
+        let code = &self.config.cat
+            .get(&part.category)
+            .ok_or_else(|| Error::Validation(format!("Category '{}' does not match config file", part.category)))?
+            .code;
+        part.id = Some(store.next_id(code)?);
+        part.validate(&self.config)?;
+
+        store.insert(&part)?;
+        Ok(part)
+    }
+
+    /// Replace the part with the same ID, which may move it to another
+    /// category
+    pub fn update_part(&self, part: Part) -> Result<()> {
+        part.validate(&self.config)?;
+        self.store()?.update(&part)
+    }
+
+    /// Delete a part, returning it
+    pub fn remove_part(&self, id: &str) -> Result<Part> {
+        self.store()?.delete(id)
+    }
+
+    /// Rewrite the parts of every category in `categories`, for saving
+    /// changes made to many parts at once
+    pub fn save_categories(&self, parts: &[Part], categories: &[&str]) -> Result<()> {
+        self.store()?.save_categories(parts, categories)
+    }
+
+    /// Build the KiCad DB, only rebuilding tables whose CSV changed
+    pub fn build(&self) -> Result<BuildReport> {
+        data::build_kicad_db(self.parts()?, &self.config)
+    }
+
+    /// Delete the KiCad DB and rebuild every table
+    pub fn rebuild(&self) -> Result<BuildReport> {
+        data::rebuild_kicad_db(self.parts()?, &self.config)
+    }
+
+    /// DigiKey API client using the library's credentials and locale
+    pub fn digikey(&self) -> Result<digikey::Client> {
+        digikey::Client::from_config(&self.config)
+    }
+
+    /// LCSC catalog client
+    pub fn lcsc(&self) -> lcsc::Client {
+        lcsc::Client::from_config(&self.config)
+    }
+}
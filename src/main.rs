@@ -1,42 +1,66 @@
 use std::time::Instant;
 use clap::Parser;
 
-mod config;
-mod data;
 mod cli;
-mod part;
-mod editor;
-mod api;
-mod util;
+mod output;
 
 use cli::{Cli, Cmd};
-use config::Config;
+use output::OutputFormat;
+use partman::{config::UserConfig, Error, Library, Result};
 
+fn main() {
+    let start_time = Instant::now();
+    let cli = Cli::parse();
+    let user_output = UserConfig::load().ok().and_then(|user| user.output);
+    let output = cli.output.or(user_output).unwrap_or_default();
 
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = core::result::Result<T, Error>;
+    if let Err(err) = run(cli.command, output) {
+        if output.is_json() {
+            // Check results are already on stdout as the command's output
+            if !matches!(err, Error::CheckFailed { .. }) {
+                println!("{}", output::error_json(&err));
+            }
+        } else {
+            eprintln!("Error: {err}");
+            if let Some(hint) = err.hint() {
+                eprintln!("  hint: {hint}");
+            }
+        }
+        std::process::exit(err.exit_code());
+    }
 
-fn main() -> Result<()> {
-    let start_time = Instant::now();
+    if !output.is_json() {
+        let elapsed = start_time.elapsed();
+        eprintln!("  Time elapsed:        {:.2?}", elapsed);
+    }
+}
 
-    match Cli::parse().command {
-        Cmd::Init(subcmd) => subcmd.run()?,
-        Cmd::Add(subcmd) => {
-            let config = Config::find_and_load()?;
-            subcmd.run(&config)?
-        },
-        Cmd::Build(subcmd) => {
-            let config = Config::find_and_load()?;
-            subcmd.run(&config)?
-        },
-        Cmd::Resume(subcmd) => {
-            let config = Config::find_and_load()?;
-            subcmd.run(&config)?
-        },
+fn run(command: Cmd, output: OutputFormat) -> Result<()> {
+    // Init works without a library and config check must open a broken one
+    let command = match command {
+        Cmd::Init(subcmd) => return subcmd.run(output),
+        Cmd::Config(subcmd) => return subcmd.run(&Library::open_unchecked()?, output),
+        command => command,
     };
 
-    let elapsed = start_time.elapsed();
-    println!("  Time elapsed:        {:.2?}", elapsed);
-
-    Ok(())
+    let library = Library::open()?;
+    match command {
+        Cmd::Init(_) | Cmd::Config(_) => unreachable!(),
+        Cmd::Add(subcmd) => subcmd.run(&library, output),
+        Cmd::Build(subcmd) => subcmd.run(&library, output),
+        Cmd::Resume(subcmd) => subcmd.run(&library, output),
+        Cmd::History(subcmd) => subcmd.run(&library, output),
+        Cmd::Refresh(subcmd) => subcmd.run(&library, output),
+        Cmd::Report(subcmd) => subcmd.run(&library, output),
+        Cmd::Jlc(subcmd) => subcmd.run(&library, output),
+        Cmd::Crossref(subcmd) => subcmd.run(&library, output),
+        Cmd::Datasheets(subcmd) => subcmd.run(&library, output),
+        Cmd::Bom(subcmd) => subcmd.run(&library, output),
+        Cmd::Order(subcmd) => subcmd.run(&library, output),
+        Cmd::Cost(subcmd) => subcmd.run(&library, output),
+        Cmd::Stock(subcmd) => subcmd.run(&library, output),
+        Cmd::Kit(subcmd) => subcmd.run(&library, output),
+        Cmd::Convert(subcmd) => subcmd.run(&library, output),
+        Cmd::Fmt(subcmd) => subcmd.run(&library, output),
+    }
 }
@@ -0,0 +1,275 @@
+//! Supplier order files for building a design.
+//!
+//! Each BOM line is assigned to the first supplier in `[order]
+//! preferred_suppliers` the part has a supplier part number for, then one
+//! upload file is written per supplier in the format its BOM or cart tool
+//! imports.
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+use csv::WriterBuilder;
+use serde::Serialize;
+
+use crate::{bom::BomLine, config::Config, part::Part, Result};
+
+/// A supplier with a known order file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Supplier {
+    Digikey,
+    Mouser,
+    Lcsc,
+}
+
+impl Supplier {
+    const ALL: [Supplier; 3] = [Supplier::Digikey, Supplier::Mouser, Supplier::Lcsc];
+
+    /// Parse a supplier name, ignoring case and punctuation
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name: String = name.chars().filter(|c| c.is_alphanumeric()).collect();
+        Self::ALL.into_iter().find(|s| s.key().eq_ignore_ascii_case(&name))
+    }
+
+    /// Name as matched against the part's supplier columns
+    fn key(self) -> &'static str {
+        match self {
+            Supplier::Digikey => "digikey",
+            Supplier::Mouser => "mouser",
+            Supplier::Lcsc => "lcsc",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Supplier::Digikey => "Digi-Key",
+            Supplier::Mouser => "Mouser",
+            Supplier::Lcsc => "LCSC",
+        }
+    }
+
+    /// Suppliers from `[order] preferred_suppliers`, in order. Unknown
+    /// names are rejected when the config is loaded.
+    pub fn preferred(config: &Config) -> Vec<Self> {
+        config.order.preferred_suppliers.iter()
+            .filter_map(|name| Self::from_name(name))
+            .collect()
+    }
+}
+
+/// The first preferred supplier the part has a part number for
+pub fn assign<'a>(part: &'a Part, preferred: &[Supplier]) -> Option<(Supplier, &'a str)> {
+    preferred.iter().find_map(|&s| Some((s, part.supplier_spn(s.key())?)))
+}
+
+/// Why a part could not be assigned to a preferred supplier
+pub fn unassigned_reason(part: &Part) -> String {
+    let others: Vec<&str> = [(&part.supplier1, &part.spn1), (&part.supplier2, &part.spn2)]
+        .into_iter()
+        .filter(|(_, spn)| spn.is_some())
+        .map(|(supplier, _)| supplier.as_deref().unwrap_or("unnamed supplier"))
+        .collect();
+    if others.is_empty() {
+        "no supplier part number".to_owned()
+    } else {
+        format!("no part number for a preferred supplier, has {}", others.join(", "))
+    }
+}
+
+/// A line to order from a supplier
+#[derive(Debug, Serialize)]
+pub struct OrderLine {
+    pub id: String,
+    pub spn: String,
+    pub mpn: Option<String>,
+    pub manufacturer: Option<String>,
+    pub description: Option<String>,
+    /// Quantity for every board
+    pub qty: usize,
+    pub references: Vec<String>,
+}
+
+/// A BOM line that could not be assigned to a supplier
+#[derive(Debug, Serialize)]
+pub struct Unassigned {
+    pub id: Option<String>,
+    pub lib_id: String,
+    pub value: String,
+    pub qty: usize,
+    pub references: Vec<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct OrderPlan {
+    pub orders: BTreeMap<Supplier, Vec<OrderLine>>,
+    pub unassigned: Vec<Unassigned>,
+}
+
+/// Assign BOM lines for `boards` boards to suppliers in order of preference
+pub fn plan(lines: &[BomLine], preferred: &[Supplier], boards: u32) -> OrderPlan {
+    let mut plan = OrderPlan::default();
+    let boards = boards as usize;
+
+    for line in lines {
+        let unassigned = |reason: &str| Unassigned {
+            id: line.id.clone(),
+            lib_id: line.lib_id.clone(),
+            value: line.value.clone(),
+            qty: line.qty * boards,
+            references: line.references.clone(),
+            reason: reason.to_owned(),
+        };
+        let Some(part) = &line.part else {
+            plan.unassigned.push(unassigned("not in library"));
+            continue;
+        };
+
+        let Some((supplier, spn)) = assign(part, preferred) else {
+            plan.unassigned.push(unassigned(&unassigned_reason(part)));
+            continue;
+        };
+
+        plan.orders.entry(supplier).or_default().push(OrderLine {
+            id: part.id.clone().unwrap_or_default(),
+            spn: spn.to_owned(),
+            mpn: part.mpn.clone(),
+            manufacturer: part.manufacturer.clone(),
+            description: line.description.clone(),
+            qty: line.qty * boards,
+            references: line.references.clone(),
+        });
+    }
+    plan
+}
+
+/// Write a supplier's upload file, returning its path
+pub fn write_file(dir: &Path, stem: &str, supplier: Supplier, lines: &[OrderLine]) -> Result<PathBuf> {
+    let path = dir.join(format!("{stem}-{}.csv", supplier.key()));
+    let mut wtr = WriterBuilder::new().from_path(&path)?;
+
+    let opt = |s: &Option<String>| s.clone().unwrap_or_default();
+    match supplier {
+        // DigiKey BOM manager upload
+        Supplier::Digikey => {
+            wtr.write_record([
+                "Quantity", "Digi-Key Part Number", "Manufacturer Part Number", "Manufacturer",
+                "Customer Reference", "Reference Designator",
+            ])?;
+            for line in lines {
+                wtr.write_record([
+                    line.qty.to_string(), line.spn.clone(), opt(&line.mpn), opt(&line.manufacturer),
+                    line.id.clone(), line.references.join(","),
+                ])?;
+            }
+        },
+        // Mouser cart and BOM tool import
+        Supplier::Mouser => {
+            wtr.write_record([
+                "Mouser Part Number", "Manufacturer Part Number", "Manufacturer", "Quantity",
+                "Customer Part Number", "Description",
+            ])?;
+            for line in lines {
+                wtr.write_record([
+                    line.spn.clone(), opt(&line.mpn), opt(&line.manufacturer), line.qty.to_string(),
+                    line.id.clone(), opt(&line.description),
+                ])?;
+            }
+        },
+        // LCSC BOM tool, also accepted by JLCPCB parts pre-ordering
+        Supplier::Lcsc => {
+            wtr.write_record([
+                "Quantity", "LCSC Part Number", "Manufacture Part Number", "Manufacturer",
+                "Customer Part Number", "Designator",
+            ])?;
+            for line in lines {
+                wtr.write_record([
+                    line.qty.to_string(), line.spn.clone(), opt(&line.mpn), opt(&line.manufacturer),
+                    line.id.clone(), line.references.join(","),
+                ])?;
+            }
+        },
+    }
+    wtr.flush()?;
+    Ok(path)
+}
+
+/// Write a JLCPCB assembly BOM for one board, for every line with an LCSC
+/// part number whichever supplier it is ordered from
+pub fn write_jlc_bom(dir: &Path, stem: &str, lines: &[BomLine]) -> Result<Option<PathBuf>> {
+    let rows: Vec<(&BomLine, &str)> = lines.iter()
+        .filter_map(|l| Some((l, l.part.as_ref()?.lcsc_spn()?)))
+        .collect();
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let path = dir.join(format!("{stem}-jlc-bom.csv"));
+    let mut wtr = WriterBuilder::new().from_path(&path)?;
+    wtr.write_record(["Comment", "Designator", "Footprint", "LCSC Part #"])?;
+    for (line, lcsc) in rows {
+        let footprint = line.part.as_ref().and_then(|p| p.footprint.clone()).unwrap_or_default();
+        // JLCPCB expects the footprint name without the library nickname
+        let footprint = footprint.rsplit(':').next().unwrap_or_default();
+        wtr.write_record([line.value.as_str(), &line.references.join(","), footprint, lcsc])?;
+    }
+    wtr.flush()?;
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bom::{self, Component};
+
+    fn lines(parts: &[Part]) -> Vec<BomLine> {
+        let components: Vec<Component> = ["CAP-0001", "CAP-0001", "CAP-0002", "CAP-0003", "CAP-0009"].iter()
+            .enumerate()
+            .map(|(i, id)| Component {
+                reference: format!("C{}", i + 1),
+                lib_id: format!("Partman:{id}"),
+                value: "100n".into(),
+                fields: Default::default(),
+            })
+            .collect();
+        bom::build(&components, parts)
+    }
+
+    #[test]
+    fn names_ignore_case_and_punctuation() {
+        assert_eq!(Supplier::from_name("Digi-Key"), Some(Supplier::Digikey));
+        assert_eq!(Supplier::from_name("mouser"), Some(Supplier::Mouser));
+        assert_eq!(Supplier::from_name("Farnell"), None);
+    }
+
+    #[test]
+    fn assigns_lines_to_preferred_suppliers() {
+        let mut both = Part::for_test("CAP-0001", "capacitor");
+        both.supplier1 = Some("Digi-Key".into());
+        both.spn1 = Some("1276-1001-1-ND".into());
+        both.supplier2 = Some("LCSC".into());
+        both.spn2 = Some("C1525".into());
+        let mut lcsc = Part::for_test("CAP-0002", "capacitor");
+        lcsc.supplier2 = Some("LCSC".into());
+        lcsc.spn2 = Some("C15849".into());
+        let mut farnell = Part::for_test("CAP-0003", "capacitor");
+        farnell.supplier1 = Some("Farnell".into());
+        farnell.spn1 = Some("1759265".into());
+        let parts = [both, lcsc, farnell];
+
+        let lcsc_first = plan(&lines(&parts), &[Supplier::Lcsc, Supplier::Digikey], 10);
+        let lcsc: Vec<(&str, &str, usize)> = lcsc_first.orders[&Supplier::Lcsc].iter()
+            .map(|l| (l.id.as_str(), l.spn.as_str(), l.qty))
+            .collect();
+        assert_eq!(lcsc, [("CAP-0001", "C1525", 20), ("CAP-0002", "C15849", 10)]);
+        assert!(!lcsc_first.orders.contains_key(&Supplier::Digikey));
+
+        let digikey_only = plan(&lines(&parts), &[Supplier::Digikey], 1);
+        assert_eq!(digikey_only.orders[&Supplier::Digikey][0].spn, "1276-1001-1-ND");
+        let reasons: Vec<&str> = digikey_only.unassigned.iter().map(|u| u.reason.as_str()).collect();
+        assert_eq!(reasons, [
+            "no part number for a preferred supplier, has LCSC",
+            "no part number for a preferred supplier, has Farnell",
+            "not in library",
+        ]);
+    }
+}
@@ -0,0 +1,60 @@
+//! Output formatting for command results.
+//!
+//! Commands print human readable text by default. With `--output json`
+//! each command prints a single JSON document on stdout instead, and
+//! errors are printed as `{"error": {"code": ..., "message": ..., "hint": ...}}`.
+
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use serde::Serialize;
+
+use partman::{Error, Result};
+
+pub use partman::config::OutputFormat;
+
+/// Parser for `--output`, listing the formats in `--help`
+pub fn parser() -> impl TypedValueParser<Value = OutputFormat> {
+    PossibleValuesParser::new(["text", "json"]).map(|s| match s.as_str() {
+        "json" => OutputFormat::Json,
+        _ => OutputFormat::Text,
+    })
+}
+
+pub trait Emit {
+    /// Print `value` as JSON in JSON mode, otherwise run `text` to print
+    /// the human readable form
+    fn emit<T: Serialize>(self, value: &T, text: impl FnOnce()) -> Result<()>;
+}
+
+impl Emit for OutputFormat {
+    fn emit<T: Serialize>(self, value: &T, text: impl FnOnce()) -> Result<()> {
+        match self {
+            OutputFormat::Text => text(),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: String,
+    hint: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ErrorReport<'a> {
+    error: ErrorBody<'a>,
+}
+
+/// Render an error as a JSON object
+pub fn error_json(err: &Error) -> String {
+    let report = ErrorReport {
+        error: ErrorBody {
+            code: err.code(),
+            message: err.to_string(),
+            hint: err.hint(),
+        },
+    };
+    serde_json::to_string_pretty(&report).unwrap_or_default()
+}
@@ -1,6 +1,8 @@
-use crate::{config::Config, editor::Form, Result, util::Normalise};
+use serde::Serialize;
 
-#[derive(Debug, Clone)]
+use crate::{config::Config, editor::Form, Error, Result, util::Normalise};
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Part {
     pub id: Option<String>,
     pub category: String,
@@ -19,6 +21,27 @@ pub struct Part {
     pub datasheet: Option<String>,
     pub status: String,
     pub notes: Option<String>,
+    pub supplier_data: SupplierData,
+    /// JLCPCB assembly library type, "basic", "extended" or "preferred"
+    pub jlc_type: Option<String>,
+    /// Downloaded datasheet file name, relative to the datasheet directory
+    pub datasheet_file: Option<String>,
+    /// SHA-256 of the downloaded datasheet, lowercase hex
+    pub datasheet_sha256: Option<String>,
+    /// URL the downloaded datasheet came from
+    pub datasheet_source: Option<String>,
+}
+
+/// Stock, price and lifecycle data recorded by `partman refresh`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SupplierData {
+    pub unit_price: Option<f64>,
+    pub qty_available: Option<u32>,
+    pub product_status: Option<String>,
+    pub discontinued: Option<bool>,
+    pub end_of_life: Option<bool>,
+    /// UTC time of the last refresh, `YYYY-MM-DD HH:MM`
+    pub last_checked: Option<String>,
 }
 
 
@@ -41,27 +64,92 @@ impl From<Form> for Part {
             footprint: value.library.footprint.none_if_empty(),
             datasheet: value.component.datasheet.none_if_empty(),
             status: value.library.status,
-            notes: None,
+            notes: value.library.notes.none_if_empty(),
+            supplier_data: SupplierData::default(),
+            jlc_type: None,
+            datasheet_file: None,
+            datasheet_sha256: None,
+            datasheet_source: None,
         }
     }
 }
 
 impl Part {
+    /// Status used for parts that can no longer be bought
+    pub const OBSOLETE: &str = "obsolete";
+
+    /// Status used for parts with known problems
+    pub const ISSUES: &str = "issues";
+
+    /// The part number for a supplier, from whichever slot holds it.
+    /// Supplier names are compared ignoring case and punctuation so
+    /// "Digi-Key" matches "digikey"
+    pub fn supplier_spn(&self, supplier: &str) -> Option<&str> {
+        let matches = |s: &Option<String>| s.as_deref().is_some_and(|s| {
+            s.chars().filter(|c| c.is_alphanumeric()).collect::<String>().eq_ignore_ascii_case(supplier)
+        });
+        if matches(&self.supplier1) {
+            self.spn1.as_deref()
+        } else if matches(&self.supplier2) {
+            self.spn2.as_deref()
+        } else {
+            None
+        }
+    }
+
+    /// The DigiKey part number, if the part has one
+    pub fn digikey_spn(&self) -> Option<&str> {
+        self.supplier_spn("digikey")
+    }
+
+    /// The LCSC part number, if the part has one
+    pub fn lcsc_spn(&self) -> Option<&str> {
+        self.supplier_spn("lcsc")
+    }
+
     pub fn validate(&self, config: &Config) -> Result<()>{
 
         // Check ID has been assigned
         if self.id.is_none() {
-            return Err("Validation error: ID was not assigned to Part".into());
+            return Err(Error::Validation("ID was not assigned to Part".into()));
         }
 
         // Check category is one of the correct categories
         let correct_categoy = config.cat.keys().any(|s| s == &self.category);
         if !correct_categoy {
-            return Err("Validation error: Category does not match config file".into());
+            return Err(Error::Validation(format!("Category '{}' does not match config file", self.category)));
+        }
+
+        // Check required fields have been filled in
+        let required = [
+            ("subcategory", &self.subcategory),
+            ("description", &self.description),
+            ("value", &self.value),
+            ("symbol", &self.symbol),
+            ("status", &self.status),
+        ];
+        for (name, field) in required {
+            if field.trim().is_empty() {
+                return Err(Error::Validation(format!("Required field '{name}' is empty")));
+            }
         }
-        
 
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+impl Part {
+    /// A part with the given ID and category and everything else empty,
+    /// for tests
+    pub(crate) fn for_test(id: &str, category: &str) -> Self {
+        let mut part = Part::from(Form::blank());
+        part.id = Some(id.to_owned());
+        part.category = category.to_owned();
+        part.subcategory = String::from("chip");
+        part.description = format!("{id} description");
+        part.symbol = String::from("Device:R");
+        part
+    }
+}
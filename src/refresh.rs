@@ -0,0 +1,235 @@
+//! Refresh stock, price and lifecycle data for existing parts from DigiKey.
+
+use serde::Serialize;
+
+use crate::{
+    api::digikey::{self, DigikeyProduct},
+    datasheet,
+    part::{Part, SupplierData},
+    util::{format_timestamp, unix_now},
+};
+
+/// Outcome of refreshing a single part
+#[derive(Debug, Serialize)]
+pub struct RefreshResult {
+    pub id: String,
+    pub spn: String,
+    pub previous: SupplierData,
+    pub current: Option<SupplierData>,
+    /// Supplier lifecycle differs from the last refresh
+    pub lifecycle_changed: bool,
+    /// Part status was changed to obsolete
+    pub marked_obsolete: bool,
+    pub error: Option<String>,
+    /// Datasheet archive outcome, when datasheets are synced too
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub datasheet: Option<datasheet::Outcome>,
+}
+
+impl From<&DigikeyProduct> for SupplierData {
+    fn from(product: &DigikeyProduct) -> Self {
+        Self {
+            unit_price: Some(product.unit_price),
+            qty_available: Some(product.quantity_available),
+            product_status: Some(product.product_status.status.clone()),
+            discontinued: Some(product.discontinued),
+            end_of_life: Some(product.end_of_life),
+            last_checked: Some(format_timestamp(unix_now())),
+        }
+    }
+}
+
+impl SupplierData {
+    /// Supplier reports the part as discontinued, end of life or obsolete
+    pub fn is_unavailable(&self) -> bool {
+        self.discontinued == Some(true)
+            || self.end_of_life == Some(true)
+            || self.product_status.as_deref().is_some_and(|s| s.eq_ignore_ascii_case("obsolete"))
+    }
+
+    /// Lifecycle fields differ from a previous refresh, never true for the
+    /// first refresh of a part
+    pub fn lifecycle_changed_from(&self, previous: &SupplierData) -> bool {
+        previous.last_checked.is_some()
+            && (self.product_status != previous.product_status
+                || self.discontinued != previous.discontinued
+                || self.end_of_life != previous.end_of_life)
+    }
+}
+
+/// Refresh a part in place, returning `None` if it has no DigiKey SPN
+pub fn refresh_part(client: &digikey::Client, part: &mut Part, mark_obsolete: bool) -> Option<RefreshResult> {
+    let spn = part.digikey_spn()?.to_owned();
+    let mut result = RefreshResult {
+        id: part.id.clone().unwrap_or_default(),
+        spn,
+        previous: part.supplier_data.clone(),
+        current: None,
+        lifecycle_changed: false,
+        marked_obsolete: false,
+        error: None,
+        datasheet: None,
+    };
+
+    let product = match client.get_product(&result.spn) {
+        Ok(product) => product,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return Some(result);
+        },
+    };
+
+    let current = SupplierData::from(&product);
+    result.lifecycle_changed = current.lifecycle_changed_from(&result.previous);
+    if mark_obsolete && current.is_unavailable() && !part.status.eq_ignore_ascii_case(Part::OBSOLETE) {
+        part.status = Part::OBSOLETE.to_owned();
+        result.marked_obsolete = true;
+    }
+    part.supplier_data = current.clone();
+    result.current = Some(current);
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+    use crate::config::DigikeySettings;
+
+    /// A DigiKey client whose 1276-1001-1-ND has product status `status`,
+    /// every other SPN is not found
+    fn digikey(status: &str, discontinued: bool) -> digikey::Client {
+        let product = serde_json::json!({
+            "Product": {
+                "Description": { "ProductDescription": "CAP CER 100NF", "DetailedDescription": "" },
+                "Manufacturer": { "Name": "Samsung" },
+                "ManufacturerProductNumber": "CL05B104KO5NNNC",
+                "UnitPrice": 0.1,
+                "DatasheetUrl": null,
+                "ProductVariations": [],
+                "NormallyStocking": true,
+                "Discontinued": discontinued,
+                "EndOfLife": false,
+                "Parameters": [],
+                "QuantityAvailable": 5000,
+                "ProductStatus": { "Status": status },
+                "Category": { "Name": "Capacitors", "ChildCategories": [] },
+            },
+        }).to_string();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 4096];
+                let len = stream.read(&mut request).unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..len]);
+                let (status, body) = if request.starts_with("POST /v1/oauth2/token") {
+                    ("200 OK", r#"{"access_token":"token"}"#.to_owned())
+                } else if request.starts_with("GET /products/v4/search/1276-1001-1-ND/") {
+                    ("200 OK", product.clone())
+                } else {
+                    ("404 Not Found", "{}".to_owned())
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+        let settings = DigikeySettings { base_url: Some(base_url), ..Default::default() };
+        digikey::Client::new("id", "secret", &settings)
+    }
+
+    fn part(spn: Option<&str>) -> Part {
+        let mut part = Part::for_test("CAP-0001", "capacitor");
+        part.supplier1 = Some("Digi-Key".into());
+        part.spn1 = spn.map(String::from);
+        part
+    }
+
+    fn checked(status: &str) -> SupplierData {
+        SupplierData {
+            product_status: Some(status.into()),
+            discontinued: Some(false),
+            end_of_life: Some(false),
+            last_checked: Some("2025-01-01 00:00".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unavailable_parts() {
+        assert!(!checked("Active").is_unavailable());
+        assert!(checked("OBSOLETE").is_unavailable());
+        assert!(SupplierData { end_of_life: Some(true), ..checked("Active") }.is_unavailable());
+        assert!(SupplierData { discontinued: Some(true), ..checked("Active") }.is_unavailable());
+        assert!(!SupplierData::default().is_unavailable());
+    }
+
+    #[test]
+    fn lifecycle_changes_need_a_previous_refresh() {
+        let current = checked("Last Time Buy");
+        assert!(current.lifecycle_changed_from(&checked("Active")));
+        assert!(!current.lifecycle_changed_from(&checked("Last Time Buy")));
+        assert!(!current.lifecycle_changed_from(&SupplierData::default()));
+        let price_only = SupplierData { unit_price: Some(1.0), qty_available: Some(0), ..checked("Active") };
+        assert!(!price_only.lifecycle_changed_from(&checked("Active")));
+    }
+
+    #[test]
+    fn skips_parts_without_a_digikey_spn() {
+        let client = digikey("Active", false);
+        assert!(refresh_part(&client, &mut part(None), true).is_none());
+    }
+
+    #[test]
+    fn records_supplier_data() {
+        let client = digikey("Active", false);
+        let mut part = part(Some("1276-1001-1-ND"));
+        part.supplier_data = checked("Active");
+        let result = refresh_part(&client, &mut part, true).unwrap();
+
+        assert!(result.error.is_none());
+        assert!(!result.lifecycle_changed && !result.marked_obsolete);
+        assert_eq!(result.previous, checked("Active"));
+        assert_eq!(part.supplier_data.qty_available, Some(5000));
+        assert_eq!(part.supplier_data.unit_price, Some(0.1));
+        assert_eq!(result.current.as_ref(), Some(&part.supplier_data));
+        assert_ne!(part.status, Part::OBSOLETE);
+    }
+
+    #[test]
+    fn marks_unavailable_parts_obsolete_only_when_asked() {
+        let client = digikey("Active", true);
+        let mut kept = part(Some("1276-1001-1-ND"));
+        kept.supplier_data = checked("Active");
+        let result = refresh_part(&client, &mut kept, false).unwrap();
+        assert!(result.lifecycle_changed && !result.marked_obsolete);
+        assert_ne!(kept.status, Part::OBSOLETE);
+
+        let mut marked = part(Some("1276-1001-1-ND"));
+        let result = refresh_part(&client, &mut marked, true).unwrap();
+        assert!(result.marked_obsolete && !result.lifecycle_changed);
+        assert_eq!(marked.status, Part::OBSOLETE);
+    }
+
+    #[test]
+    fn reports_lookup_errors_without_changing_the_part() {
+        let client = digikey("Active", false);
+        let mut part = part(Some("399-NOPE-ND"));
+        part.supplier_data = checked("Active");
+        let result = refresh_part(&client, &mut part, true).unwrap();
+
+        assert!(result.error.as_deref().is_some_and(|e| e.contains("404")));
+        assert!(result.current.is_none());
+        assert_eq!(part.supplier_data, checked("Active"));
+    }
+}
@@ -0,0 +1,184 @@
+//! Reorder list for parts whose stock on hand fell below their minimum.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::{
+    api::digikey,
+    cost,
+    data::{StockLevel, StockSummary},
+    order::{self, OrderLine, Supplier},
+    part::Part,
+};
+
+/// A part below its minimum stock level
+#[derive(Debug, Serialize)]
+pub struct ReorderLine {
+    pub id: String,
+    pub description: Option<String>,
+    pub mpn: Option<String>,
+    pub manufacturer: Option<String>,
+    pub on_hand: u32,
+    pub min: u32,
+    pub target: u32,
+    /// Quantity needed to reach the target
+    pub needed: u32,
+    /// Quantity to order, raised to the supplier's minimum order quantity
+    pub order_qty: u32,
+    pub supplier: Option<Supplier>,
+    pub spn: Option<String>,
+    pub moq: Option<u32>,
+    /// Why the line can't be ordered or the MOQ is unknown
+    pub note: Option<String>,
+}
+
+/// Lines for every part below its minimum, with order quantities up to the
+/// target. DigiKey lines are looked up for their MOQ unless `client` is None.
+pub fn reorder(
+    levels: &[StockLevel],
+    stock: &BTreeMap<String, StockSummary>,
+    parts: &[Part],
+    preferred: &[Supplier],
+    client: Option<&digikey::Client>,
+) -> Vec<ReorderLine> {
+    let by_id: HashMap<&str, &Part> = parts.iter()
+        .filter_map(|p| Some((p.id.as_deref()?, p)))
+        .collect();
+
+    let mut lines = Vec::new();
+    for level in levels {
+        let on_hand = stock.get(&level.id).map(|s| s.qty).unwrap_or_default();
+        if on_hand >= level.min {
+            continue;
+        }
+        let needed = level.target.max(level.min).saturating_sub(on_hand);
+        let mut line = ReorderLine {
+            id: level.id.clone(),
+            description: None,
+            mpn: None,
+            manufacturer: None,
+            on_hand,
+            min: level.min,
+            target: level.target,
+            needed,
+            order_qty: needed,
+            supplier: None,
+            spn: None,
+            moq: None,
+            note: None,
+        };
+
+        let Some(part) = by_id.get(level.id.as_str()) else {
+            line.note = Some("not in library".into());
+            lines.push(line);
+            continue;
+        };
+        line.description = Some(part.description.clone());
+        line.mpn = part.mpn.clone();
+        line.manufacturer = part.manufacturer.clone();
+        let Some((supplier, spn)) = order::assign(part, preferred) else {
+            line.note = Some(order::unassigned_reason(part));
+            lines.push(line);
+            continue;
+        };
+        line.supplier = Some(supplier);
+        line.spn = Some(spn.to_owned());
+
+        if let (Supplier::Digikey, Some(client)) = (supplier, client) {
+            let moq = client.get_product(spn)
+                .and_then(|product| Ok(cost::find_variation(&product, spn)?.minimum_order_qty));
+            match moq {
+                Ok(moq) => {
+                    line.moq = Some(moq);
+                    line.order_qty = needed.max(moq);
+                },
+                Err(e) => line.note = Some(format!("MOQ lookup failed: {e}")),
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Order lines grouped by supplier, for writing cart files
+pub fn cart(lines: &[ReorderLine]) -> BTreeMap<Supplier, Vec<OrderLine>> {
+    let mut carts: BTreeMap<Supplier, Vec<OrderLine>> = BTreeMap::new();
+    for line in lines {
+        let (Some(supplier), Some(spn)) = (line.supplier, &line.spn) else { continue };
+        carts.entry(supplier).or_default().push(OrderLine {
+            id: line.id.clone(),
+            spn: spn.clone(),
+            mpn: line.mpn.clone(),
+            manufacturer: line.manufacturer.clone(),
+            description: line.description.clone(),
+            qty: line.order_qty as usize,
+            references: Vec::new(),
+        });
+    }
+    carts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(id: &str, min: u32, target: u32) -> StockLevel {
+        StockLevel { id: id.into(), min, target }
+    }
+
+    fn stock(entries: &[(&str, u32)]) -> BTreeMap<String, StockSummary> {
+        entries.iter()
+            .map(|&(id, qty)| (id.to_owned(), StockSummary { qty, locations: vec!["bin".into()] }))
+            .collect()
+    }
+
+    fn parts() -> Vec<Part> {
+        let mut digikey = Part::for_test("RES-0001", "resistor");
+        digikey.mpn = Some("RC0402FR-07120KL".into());
+        digikey.manufacturer = Some("YAGEO".into());
+        digikey.supplier1 = Some("Digi-Key".into());
+        digikey.spn1 = Some("311-120KLRCT-ND".into());
+        let mut lcsc = Part::for_test("CAP-0001", "capacitor");
+        lcsc.supplier2 = Some("LCSC".into());
+        lcsc.spn2 = Some("C1525".into());
+        vec![digikey, lcsc, Part::for_test("CAP-0002", "capacitor")]
+    }
+
+    #[test]
+    fn lists_parts_below_their_minimum() {
+        let levels = [
+            level("RES-0001", 100, 500),
+            level("CAP-0001", 50, 0),
+            level("CAP-0002", 10, 20),
+            level("IND-0001", 1, 5),
+        ];
+        let stock = stock(&[("RES-0001", 40), ("CAP-0001", 20), ("CAP-0002", 10)]);
+        let lines = reorder(&levels, &stock, &parts(), &[Supplier::Digikey, Supplier::Lcsc], None);
+
+        let needed: Vec<(&str, u32, u32)> = lines.iter().map(|l| (l.id.as_str(), l.on_hand, l.needed)).collect();
+        // A target below the minimum orders up to the minimum
+        assert_eq!(needed, [("RES-0001", 40, 460), ("CAP-0001", 20, 30), ("IND-0001", 0, 5)]);
+
+        assert_eq!(lines[0].supplier, Some(Supplier::Digikey));
+        assert_eq!(lines[0].mpn.as_deref(), Some("RC0402FR-07120KL"));
+        assert_eq!((lines[0].order_qty, lines[0].moq), (460, None));
+        assert_eq!(lines[1].spn.as_deref(), Some("C1525"));
+        assert_eq!(lines[2].note.as_deref(), Some("not in library"));
+    }
+
+    #[test]
+    fn cart_groups_orderable_lines_by_supplier() {
+        let levels = [level("RES-0001", 100, 500), level("CAP-0001", 50, 100), level("CAP-0002", 10, 20)];
+        let mut lines = reorder(&levels, &stock(&[]), &parts(), &[Supplier::Lcsc, Supplier::Digikey], None);
+        assert!(lines[2].supplier.is_none() && lines[2].note.is_some());
+        lines[0].order_qty = 2500;
+
+        let carts = cart(&lines);
+        assert_eq!(carts.len(), 2);
+        let digikey = &carts[&Supplier::Digikey][0];
+        assert_eq!((digikey.spn.as_str(), digikey.qty), ("311-120KLRCT-ND", 2500));
+        assert_eq!(digikey.manufacturer.as_deref(), Some("YAGEO"));
+        assert_eq!(carts[&Supplier::Lcsc][0].id, "CAP-0001");
+    }
+}
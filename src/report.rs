@@ -0,0 +1,147 @@
+//! Library reports.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use serde::Serialize;
+
+use crate::{api::digikey, part::Part};
+
+/// A problem found with a part's availability
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum Finding {
+    Discontinued,
+    EndOfLife,
+    NotNormallyStocking,
+    OutOfStock,
+    NoSupplierPartNumber,
+    LookupFailed(String),
+}
+
+impl Finding {
+    pub fn describe(&self) -> String {
+        match self {
+            Finding::Discontinued => "discontinued".into(),
+            Finding::EndOfLife => "end of life".into(),
+            Finding::NotNormallyStocking => "not normally stocking".into(),
+            Finding::OutOfStock => "out of stock".into(),
+            Finding::NoSupplierPartNumber => "no supplier part number".into(),
+            Finding::LookupFailed(e) => format!("lookup failed: {e}"),
+        }
+    }
+}
+
+/// A part with at least one finding
+#[derive(Debug, Serialize)]
+pub struct LifecycleLine {
+    pub id: String,
+    pub description: String,
+    pub mpn: Option<String>,
+    pub spn: Option<String>,
+    pub product_status: Option<String>,
+    pub qty_available: Option<u32>,
+    pub findings: Vec<Finding>,
+}
+
+/// Lifecycle report lines grouped by category
+pub type LifecycleReport = BTreeMap<String, Vec<LifecycleLine>>;
+
+/// Check every part against DigiKey, keeping only parts with findings
+pub fn lifecycle(client: &digikey::Client, parts: &[Part], mut progress: impl FnMut(&str)) -> LifecycleReport {
+    let mut report = LifecycleReport::new();
+
+    for part in parts {
+        let mut line = LifecycleLine {
+            id: part.id.clone().unwrap_or_default(),
+            description: part.description.clone(),
+            mpn: part.mpn.clone(),
+            spn: part.digikey_spn().map(str::to_owned),
+            product_status: None,
+            qty_available: None,
+            findings: Vec::new(),
+        };
+
+        if part.spn1.is_none() && part.spn2.is_none() {
+            line.findings.push(Finding::NoSupplierPartNumber);
+        }
+
+        if let Some(spn) = &line.spn {
+            progress(&line.id);
+            match client.get_product(spn) {
+                Ok(product) => {
+                    if product.discontinued {
+                        line.findings.push(Finding::Discontinued);
+                    }
+                    if product.end_of_life {
+                        line.findings.push(Finding::EndOfLife);
+                    }
+                    if !product.normally_stocking {
+                        line.findings.push(Finding::NotNormallyStocking);
+                    }
+                    if product.quantity_available == 0 {
+                        line.findings.push(Finding::OutOfStock);
+                    }
+                    line.product_status = Some(product.product_status.status);
+                    line.qty_available = Some(product.quantity_available);
+                },
+                Err(e) => line.findings.push(Finding::LookupFailed(e.to_string())),
+            }
+        }
+
+        if !line.findings.is_empty() {
+            report.entry(part.category.clone()).or_default().push(line);
+        }
+    }
+
+    for lines in report.values_mut() {
+        lines.sort_by(|a, b| a.id.cmp(&b.id));
+    }
+    report
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render a lifecycle report as a standalone HTML page
+pub fn lifecycle_html(report: &LifecycleReport, title: &str) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+         <style>body{{font-family:sans-serif}}table{{border-collapse:collapse}}\
+         td,th{{border:1px solid #ccc;padding:4px 8px;text-align:left}}</style>\n\
+         </head>\n<body>\n<h1>{0}</h1>\n",
+        escape_html(title)
+    );
+
+    if report.is_empty() {
+        html.push_str("<p>No issues found.</p>\n");
+    }
+
+    for (category, lines) in report {
+        let _ = writeln!(html, "<h2>{}</h2>", escape_html(category));
+        html.push_str("<table>\n<tr><th>ID</th><th>Description</th><th>MPN</th><th>DigiKey SPN</th><th>Status</th><th>Qty</th><th>Findings</th></tr>\n");
+        for line in lines {
+            let findings: Vec<String> = line.findings.iter().map(Finding::describe).collect();
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape_html(&line.id),
+                escape_html(&line.description),
+                escape_html(line.mpn.as_deref().unwrap_or("")),
+                escape_html(line.spn.as_deref().unwrap_or("")),
+                escape_html(line.product_status.as_deref().unwrap_or("")),
+                line.qty_available.map(|q| q.to_string()).unwrap_or_default(),
+                escape_html(&findings.join(", ")),
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}